@@ -0,0 +1,39 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use tui_syntax_highlight::Highlighter;
+
+const LINES: usize = 10_000;
+
+fn highlight_borrowed(c: &mut Criterion) {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let themes = ThemeSet::load_defaults();
+    let highlighter = Highlighter::new(themes.themes["base16-ocean.dark"].clone());
+    let syntax = syntaxes.find_syntax_by_name("Rust").unwrap();
+    let source: Vec<_> = (0..LINES)
+        .map(|i| format!("    let value_{i} = compute({i}, \"text\"); // comment"))
+        .collect();
+    let lines: Vec<_> = source.iter().map(String::as_str).collect();
+
+    let mut group = c.benchmark_group("borrowed vs owned spans");
+    group.bench_function("highlight_lines", |b| {
+        b.iter(|| {
+            highlighter
+                .highlight_lines(black_box(lines.iter().copied()), syntax, &syntaxes)
+                .unwrap()
+        });
+    });
+    group.bench_function("highlight_lines_borrowed", |b| {
+        b.iter(|| {
+            highlighter
+                .highlight_lines_borrowed(black_box(&lines), syntax, &syntaxes)
+                .unwrap()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, highlight_borrowed);
+criterion_main!(benches);
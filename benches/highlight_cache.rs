@@ -0,0 +1,53 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use tui_syntax_highlight::{Highlighter, LineCache};
+
+const LINES: usize = 10_000;
+
+fn highlight_cache(c: &mut Criterion) {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let themes = ThemeSet::load_defaults();
+    let highlighter = Highlighter::new(themes.themes["base16-ocean.dark"].clone());
+    let syntax = syntaxes.find_syntax_by_name("Rust").unwrap();
+    let source: Vec<_> = (0..LINES)
+        .map(|i| format!("    let value_{i} = compute({i}, \"text\"); // comment"))
+        .collect();
+    let mut lines: Vec<_> = source.iter().map(String::as_str).collect();
+    let edited = "    let edited = compute(0, \"text\");";
+    let edit_at = LINES - 100;
+
+    let mut group = c.benchmark_group("rehighlight after one-line edit");
+    group.bench_function("highlight_lines", |b| {
+        b.iter(|| {
+            highlighter
+                .highlight_lines(black_box(lines.iter().copied()), syntax, &syntaxes)
+                .unwrap()
+        });
+    });
+
+    let mut cache = LineCache::new();
+    highlighter
+        .highlight_with_cache(&lines, &mut cache, 0, syntax, &syntaxes)
+        .unwrap();
+    lines[edit_at] = edited;
+    group.bench_function("highlight_with_cache", |b| {
+        b.iter(|| {
+            highlighter
+                .highlight_with_cache(
+                    black_box(&lines),
+                    &mut cache,
+                    black_box(edit_at),
+                    syntax,
+                    &syntaxes,
+                )
+                .unwrap()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, highlight_cache);
+criterion_main!(benches);
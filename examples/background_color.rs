@@ -10,8 +10,7 @@ use ratatui::crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use ratatui::layout::Alignment;
-use ratatui::style::Stylize;
-use ratatui::widgets::{Block, BorderType, Paragraph};
+use ratatui::widgets::{Block, BorderType};
 use syntect_assets::assets::HighlightingAssets;
 use tui_syntax_highlight::Highlighter;
 
@@ -30,15 +29,14 @@ fn main() -> Result<()> {
     let syntax = syntaxes
         .find_syntax_by_name("Rust")
         .expect("syntax missing");
-    let highlight = highlighter.highlight_reader(
+    let highlight = highlighter.highlight_reader_widget(
         File::open("./examples/sqlite_custom/build.rs")?,
         syntax,
         &syntaxes,
     )?;
 
-    let bg = highlighter.get_background_color().unwrap_or_default();
-    // Set the background on the text container so it matches.
-    let paragraph = Paragraph::new(highlight).bg(bg).block(
+    // The paragraph gets the theme's background, so the area around the text matches.
+    let paragraph = highlight.into_paragraph().block(
         Block::bordered()
             .border_type(BorderType::Rounded)
             .title("Syntax Highlight!")
@@ -0,0 +1,44 @@
+use std::cell::LazyCell;
+use std::env;
+use std::error::Error;
+use std::io::stdout;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::{Terminal, TerminalOptions, Viewport};
+use syntect_assets::assets::HighlightingAssets;
+use tui_syntax_highlight::{Highlighter, SyntaxHint};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+thread_local! {
+    static ASSETS: LazyCell<HighlightingAssets> = LazyCell::new(HighlightingAssets::from_binary);
+}
+
+// Usage: cat src/lib.rs | cargo run --example highlight_stdin -- --language rust
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let mut language = None;
+    while let Some(arg) = args.next() {
+        if arg == "--language" {
+            language = Some(args.next().ok_or("missing value for --language")?);
+        }
+    }
+
+    let theme = ASSETS.with(|a| a.get_theme("Nord").clone());
+    let highlighter = Highlighter::new(theme);
+    let syntaxes = ASSETS.with(|a| a.get_syntax_set().cloned())?;
+    let text =
+        highlighter.highlight_stdin(language.as_deref().map(SyntaxHint::Token), &syntaxes)?;
+
+    let mut terminal = Terminal::with_options(
+        CrosstermBackend::new(stdout()),
+        TerminalOptions {
+            viewport: Viewport::Inline(text.height() as u16),
+        },
+    )?;
+    terminal.draw(|frame| {
+        frame.render_widget(text, frame.area());
+    })?;
+    println!();
+    Ok(())
+}
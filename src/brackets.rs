@@ -0,0 +1,134 @@
+use std::str::FromStr;
+
+use syntect::highlighting::ScopeSelectors;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+
+use crate::render::with_lf;
+
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Finds the bracket that pairs with the one at `cursor`, given as a line index and a character
+/// column. Returns the positions of both brackets as a line index and byte offset, or `None` if
+/// the cursor isn't on a bracket or the bracket is unmatched.
+///
+/// Brackets are only paired with brackets of the same kind, and brackets inside strings or
+/// comments are ignored.
+pub(crate) fn find_match(
+    lines: &[&str],
+    cursor: (usize, usize),
+    syntax: &SyntaxReference,
+    syntaxes: &SyntaxSet,
+) -> Result<Option<[(usize, usize); 2]>, crate::Error> {
+    let Some(cursor) = cursor_offset(lines, cursor) else {
+        return Ok(None);
+    };
+    let state = (ParseState::new(syntax), ScopeStack::new());
+    match search(lines, 0, state, cursor, syntaxes)? {
+        Search::Found(pair) => Ok(Some(pair)),
+        Search::Unmatched | Search::NeedsEarlierLines => Ok(None),
+    }
+}
+
+/// Finds the bracket that pairs with the one at `cursor` like [`find_match`], but starts parsing
+/// from a known parse state instead of the start of the source. `state_at` returns the parse
+/// state and scope stack at the start of a line, and is only called with lines up to `known`.
+///
+/// The search starts at the cursor line and only moves further back when the cursor is on a
+/// closing bracket whose pair wasn't found, so only the lines between the brackets are parsed.
+pub(crate) fn find_match_from<F>(
+    lines: &[&str],
+    cursor: (usize, usize),
+    syntaxes: &SyntaxSet,
+    known: usize,
+    state_at: F,
+) -> Result<Option<[(usize, usize); 2]>, crate::Error>
+where
+    F: Fn(usize) -> (ParseState, ScopeStack),
+{
+    let Some(cursor) = cursor_offset(lines, cursor) else {
+        return Ok(None);
+    };
+    let mut first_line = cursor.0.min(known);
+    loop {
+        match search(lines, first_line, state_at(first_line), cursor, syntaxes)? {
+            Search::Found(pair) => return Ok(Some(pair)),
+            Search::Unmatched => return Ok(None),
+            Search::NeedsEarlierLines => {
+                // Double the number of lines searched before the cursor each time.
+                first_line = first_line.saturating_sub((cursor.0 - first_line).max(1));
+            }
+        }
+    }
+}
+
+enum Search {
+    Found([(usize, usize); 2]),
+    // The cursor isn't on a bracket, or the bracket has no pair.
+    Unmatched,
+    // The cursor is on a closing bracket that may pair with a bracket before the first line that
+    // was searched.
+    NeedsEarlierLines,
+}
+
+// Converts the character column of the cursor to a byte offset.
+fn cursor_offset(lines: &[&str], (line, column): (usize, usize)) -> Option<(usize, usize)> {
+    let (offset, _) = lines.get(line)?.char_indices().nth(column)?;
+    Some((line, offset))
+}
+
+// Pairs brackets starting from `first_line`, which is parsed starting from `state`. Pairs that
+// are found are always correct, since the brackets between them are also searched.
+fn search(
+    lines: &[&str],
+    first_line: usize,
+    state: (ParseState, ScopeStack),
+    cursor: (usize, usize),
+    syntaxes: &SyntaxSet,
+) -> Result<Search, crate::Error> {
+    let ignored = ScopeSelectors::from_str("string, comment").expect("invalid scope selector");
+    let (mut parse_state, mut scopes) = state;
+    let mut open: [Vec<(usize, usize)>; 3] = Default::default();
+    let mut cursor_open = false;
+    for (line_number, line) in lines.iter().enumerate().skip(first_line) {
+        let line = with_lf(line);
+        let ops = parse_state
+            .parse_line(&line, syntaxes)
+            .map_err(|e| crate::Error::Highlight(e.into()))?;
+        let mut ops = ops.iter().peekable();
+        for (offset, c) in line.char_indices() {
+            while let Some((_, op)) = ops.next_if(|(pos, _)| *pos <= offset) {
+                scopes
+                    .apply(op)
+                    .map_err(|e| crate::Error::Highlight(e.into()))?;
+            }
+            let Some(kind) = PAIRS.iter().position(|(o, c2)| c == *o || c == *c2) else {
+                continue;
+            };
+            if ignored.does_match(scopes.as_slice()).is_some() {
+                continue;
+            }
+            let position = (line_number, offset);
+            if c == PAIRS[kind].0 {
+                cursor_open |= position == cursor;
+                open[kind].push(position);
+            } else if let Some(start) = open[kind].pop() {
+                if start == cursor || position == cursor {
+                    return Ok(Search::Found([start, position]));
+                }
+            } else if position == cursor && first_line > 0 {
+                return Ok(Search::NeedsEarlierLines);
+            }
+        }
+        for (_, op) in ops {
+            scopes
+                .apply(op)
+                .map_err(|e| crate::Error::Highlight(e.into()))?;
+        }
+        if line_number >= cursor.0 && !cursor_open {
+            // The cursor isn't on an opening bracket, and any closing bracket would have been
+            // matched by now.
+            return Ok(Search::Unmatched);
+        }
+    }
+    Ok(Search::Unmatched)
+}
@@ -0,0 +1,16 @@
+use std::ops::Range;
+
+/// An error reported by a compiler or linter, used to annotate highlighted source with
+/// `Highlighter::render_with_errors`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CompilerError {
+    /// The file the error was reported in.
+    pub file: String,
+    /// The one-based line number of the error.
+    pub line: usize,
+    /// The one-based range of columns the error applies to, measured in characters. The end of
+    /// the range is exclusive.
+    pub col_range: Range<usize>,
+    /// The error message.
+    pub message: String,
+}
@@ -1,11 +1,33 @@
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
 #[cfg(feature = "termprofile")]
 use termprofile::TermProfile;
 
-/// Converts between [`syntect`] styles and [`ratatui`](ratatui_core) styles.
+use crate::render::{ANSI16_PALETTE, CUBE_LEVELS, RunColor, StyledLine, StyledRun, with_lf};
+
+/// Converts [`syntect`] styles into ratatui styles, or into [`StyledRun`]s for renderers that
+/// don't use ratatui.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Converter {
     #[cfg(feature = "termprofile")]
     profile: TermProfile,
+    dim_low_contrast: bool,
+    depth: ColorDepth,
+}
+
+/// The number of colors available to the terminal. See [`Converter::downsample`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorDepth {
+    /// Use RGB colors as-is.
+    #[default]
+    TrueColor,
+    /// Quantize RGB colors to the 256-color palette.
+    Ansi256,
+    /// Quantize RGB and indexed colors to the 16 basic ANSI colors.
+    Ansi16,
+    /// Remove all colors.
+    Monochrome,
 }
 
 impl Default for Converter {
@@ -20,15 +42,125 @@ impl Converter {
         Self {
             #[cfg(feature = "termprofile")]
             profile: TermProfile::TrueColor,
+            dim_low_contrast: false,
+            depth: ColorDepth::TrueColor,
         }
     }
 
     /// Creates a new [`Converter`] with the given [`TermProfile`].
     #[cfg(feature = "termprofile")]
     pub fn with_profile(profile: TermProfile) -> Self {
-        Self { profile }
+        Self {
+            profile,
+            dim_low_contrast: false,
+            depth: ColorDepth::TrueColor,
+        }
+    }
+
+    /// Replace foreground colors that barely stand out from their background with a dim
+    /// modifier. Some themes express dimmed tokens (such as comments) with translucent or
+    /// low-contrast foregrounds, which can render better as dimmed text on some terminals.
+    ///
+    /// This only applies to ratatui styles. [`StyledRun`]s have no dim attribute, so they keep
+    /// the foreground color.
+    pub fn dim_low_contrast(mut self, dim_low_contrast: bool) -> Self {
+        self.dim_low_contrast = dim_low_contrast;
+        self
+    }
+
+    /// Quantize converted colors to the nearest color available at the given [`ColorDepth`]. This
+    /// is a lightweight alternative to the `termprofile` feature for terminals with a known color
+    /// depth. If a profile is also set, colors are adapted to the profile first.
+    pub fn downsample(mut self, depth: ColorDepth) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Converts the syntect [`Color`](syntect::highlighting::Color) to a [`RunColor`], quantized
+    /// to the converter's [`ColorDepth`]. Returns [`None`] if the color should use the terminal's
+    /// default.
+    pub fn syntect_color_to_run(&self, color: syntect::highlighting::Color) -> Option<RunColor> {
+        let color = RunColor::from_syntect(color)?;
+        #[cfg(feature = "termprofile")]
+        let color = match color {
+            RunColor::Rgb(r, g, b) => RunColor::from_syntect(
+                self.tui_color_to_syntect(
+                    self.profile
+                        .adapt_color(ratatui_core::style::Color::Rgb(r, g, b))?,
+                ),
+            )?,
+            color => color,
+        };
+        self.downsample_run_color(color)
+    }
+
+    /// Converts the syntect [`Style`](syntect::highlighting::Style) to a [`StyledRun`] containing
+    /// `text`, with colors converted by [`syntect_color_to_run`](Self::syntect_color_to_run).
+    pub fn syntect_style_to_run<T>(&self, style: syntect::highlighting::Style, text: T) -> StyledRun
+    where
+        T: Into<String>,
+    {
+        StyledRun {
+            text: text.into(),
+            fg: self.syntect_color_to_run(style.foreground),
+            bg: self.syntect_color_to_run(style.background),
+            bold: style.font_style.contains(FontStyle::BOLD),
+            italic: style.font_style.contains(FontStyle::ITALIC),
+            underline: style.font_style.contains(FontStyle::UNDERLINE),
+        }
+    }
+
+    /// Highlights text from an iterator like [`render::styled_lines`](crate::render::styled_lines),
+    /// converting each style with [`syntect_style_to_run`](Self::syntect_style_to_run).
+    pub fn styled_lines<'a, T>(
+        &self,
+        theme: &Theme,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Vec<StyledLine>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        source
+            .into_iter()
+            .map(|line| {
+                let line = with_lf(line);
+                let regions = highlighter
+                    .highlight_line(&line, syntaxes)
+                    .map_err(crate::Error::Highlight)?;
+                Ok(regions
+                    .into_iter()
+                    .map(|(style, text)| {
+                        self.syntect_style_to_run(style, text.trim_end_matches('\n'))
+                    })
+                    .filter(|run| !run.text.is_empty())
+                    .collect())
+            })
+            .collect()
     }
 
+    // Quantizes the color to the converter's color depth.
+    fn downsample_run_color(&self, color: RunColor) -> Option<RunColor> {
+        let rgb = match color {
+            RunColor::Indexed(0..16) => {
+                return (self.depth != ColorDepth::Monochrome).then_some(color);
+            }
+            color => color.to_rgb(),
+        };
+        match self.depth {
+            ColorDepth::TrueColor => Some(color),
+            ColorDepth::Ansi256 if matches!(color, RunColor::Indexed(_)) => Some(color),
+            ColorDepth::Ansi256 => Some(RunColor::Indexed(rgb_to_ansi256(rgb))),
+            ColorDepth::Ansi16 => Some(RunColor::Indexed(rgb_to_ansi16(rgb))),
+            ColorDepth::Monochrome => None,
+        }
+    }
+}
+
+#[cfg(feature = "ratatui")]
+impl Converter {
     /// Converts the syntect [`Style`](syntect::highlighting::Style) to a ratatui
     /// [`Style`](ratatui_core::style::Style).
     pub fn syntect_style_to_tui(
@@ -40,7 +172,9 @@ impl Converter {
             return ratatui_core::style::Style::new();
         }
         let mut tui_style = ratatui_core::style::Style::new();
-        if let Some(fg) = self.syntect_color_to_tui(style.foreground) {
+        if self.dim_low_contrast && is_low_contrast(style.foreground, style.background) {
+            tui_style = tui_style.add_modifier(ratatui_core::style::Modifier::DIM);
+        } else if let Some(fg) = self.syntect_color_to_tui(style.foreground) {
             tui_style = tui_style.fg(fg);
         }
         if let Some(bg) = self.syntect_color_to_tui(style.background) {
@@ -49,45 +183,199 @@ impl Converter {
         tui_style.add_modifier(syntect_modifiers_to_tui(&style.font_style))
     }
 
+    // Converts a theme rule's style into a ratatui style that can be patched over a token.
+    pub(crate) fn style_modifier_to_tui(
+        &self,
+        modifier: syntect::highlighting::StyleModifier,
+    ) -> ratatui_core::style::Style {
+        let mut tui_style = ratatui_core::style::Style::new();
+        if let Some(fg) = modifier
+            .foreground
+            .and_then(|fg| self.syntect_color_to_tui(fg))
+        {
+            tui_style = tui_style.fg(fg);
+        }
+        if let Some(bg) = modifier
+            .background
+            .and_then(|bg| self.syntect_color_to_tui(bg))
+        {
+            tui_style = tui_style.bg(bg);
+        }
+        if let Some(font_style) = modifier.font_style {
+            tui_style = tui_style.add_modifier(syntect_modifiers_to_tui(&font_style));
+        }
+        tui_style
+    }
+
     /// Converts the syntect [`Color`](ratatui_core::style::Color) to a ratatui
     /// [`Color`](ratatui_core::style::Color).
     pub fn syntect_color_to_tui(
         &self,
         color: syntect::highlighting::Color,
     ) -> Option<ratatui_core::style::Color> {
-        if color.a == 0 {
-            Some(match color.r {
-                0x00 => ratatui_core::style::Color::Black,
-                0x01 => ratatui_core::style::Color::Red,
-                0x02 => ratatui_core::style::Color::Green,
-                0x03 => ratatui_core::style::Color::Yellow,
-                0x04 => ratatui_core::style::Color::Blue,
-                0x05 => ratatui_core::style::Color::Magenta,
-                0x06 => ratatui_core::style::Color::Cyan,
-                0x07 => ratatui_core::style::Color::Gray,
-                0x08 => ratatui_core::style::Color::DarkGray,
-                0x09 => ratatui_core::style::Color::LightRed,
-                0x0A => ratatui_core::style::Color::LightGreen,
-                0x0B => ratatui_core::style::Color::LightYellow,
-                0x0C => ratatui_core::style::Color::LightBlue,
-                0x0D => ratatui_core::style::Color::LightMagenta,
-                0x0E => ratatui_core::style::Color::LightCyan,
-                0x0F => ratatui_core::style::Color::White,
-                c => ratatui_core::style::Color::Indexed(c),
-            })
-        } else if color.a == 1 {
-            None
-        } else {
+        let color = match RunColor::from_syntect(color)? {
+            RunColor::Indexed(index) => ansi_color_to_tui(index),
             #[cfg(feature = "termprofile")]
-            return self
+            RunColor::Rgb(r, g, b) => self
                 .profile
-                .adapt_color(ratatui_core::style::Color::Rgb(color.r, color.g, color.b));
+                .adapt_color(ratatui_core::style::Color::Rgb(r, g, b))?,
             #[cfg(not(feature = "termprofile"))]
-            return Some(ratatui_core::style::Color::Rgb(color.r, color.g, color.b));
+            RunColor::Rgb(r, g, b) => ratatui_core::style::Color::Rgb(r, g, b),
+        };
+        self.downsample_color(color)
+    }
+
+    // Quantizes the color to the converter's color depth.
+    fn downsample_color(
+        &self,
+        color: ratatui_core::style::Color,
+    ) -> Option<ratatui_core::style::Color> {
+        let color = match color {
+            ratatui_core::style::Color::Rgb(r, g, b) => RunColor::Rgb(r, g, b),
+            ratatui_core::style::Color::Indexed(index) if index >= 16 => RunColor::Indexed(index),
+            _ => return (self.depth != ColorDepth::Monochrome).then_some(color),
+        };
+        self.downsample_run_color(color).map(Into::into)
+    }
+
+    /// Converts the ratatui [`Color`](ratatui_core::style::Color) to a syntect
+    /// [`Color`](syntect::highlighting::Color), using the same encoding as ANSI themes: named and
+    /// indexed colors are stored with an alpha of `0`, [`Reset`] is stored with an alpha of `1`,
+    /// and RGB colors are stored with an alpha of `255`.
+    ///
+    /// [`Reset`]: ratatui_core::style::Color::Reset
+    pub fn tui_color_to_syntect(
+        &self,
+        color: ratatui_core::style::Color,
+    ) -> syntect::highlighting::Color {
+        let indexed = |r| syntect::highlighting::Color {
+            r,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        match color {
+            ratatui_core::style::Color::Reset => syntect::highlighting::Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 1,
+            },
+            ratatui_core::style::Color::Black => indexed(0x00),
+            ratatui_core::style::Color::Red => indexed(0x01),
+            ratatui_core::style::Color::Green => indexed(0x02),
+            ratatui_core::style::Color::Yellow => indexed(0x03),
+            ratatui_core::style::Color::Blue => indexed(0x04),
+            ratatui_core::style::Color::Magenta => indexed(0x05),
+            ratatui_core::style::Color::Cyan => indexed(0x06),
+            ratatui_core::style::Color::Gray => indexed(0x07),
+            ratatui_core::style::Color::DarkGray => indexed(0x08),
+            ratatui_core::style::Color::LightRed => indexed(0x09),
+            ratatui_core::style::Color::LightGreen => indexed(0x0A),
+            ratatui_core::style::Color::LightYellow => indexed(0x0B),
+            ratatui_core::style::Color::LightBlue => indexed(0x0C),
+            ratatui_core::style::Color::LightMagenta => indexed(0x0D),
+            ratatui_core::style::Color::LightCyan => indexed(0x0E),
+            ratatui_core::style::Color::White => indexed(0x0F),
+            ratatui_core::style::Color::Indexed(c) => indexed(c),
+            ratatui_core::style::Color::Rgb(r, g, b) => {
+                syntect::highlighting::Color { r, g, b, a: 0xFF }
+            }
         }
     }
+
+    /// Converts the ratatui [`Style`](ratatui_core::style::Style) to a syntect
+    /// [`Style`](syntect::highlighting::Style). Colors are encoded the same way as
+    /// [`tui_color_to_syntect`](Self::tui_color_to_syntect), and missing colors are stored as
+    /// [`Reset`](ratatui_core::style::Color::Reset). Only the bold, italic, and underlined
+    /// modifiers have a syntect equivalent, so any others are dropped.
+    pub fn tui_style_to_syntect(
+        &self,
+        style: ratatui_core::style::Style,
+    ) -> syntect::highlighting::Style {
+        let color = |color: Option<ratatui_core::style::Color>| {
+            self.tui_color_to_syntect(color.unwrap_or(ratatui_core::style::Color::Reset))
+        };
+        syntect::highlighting::Style {
+            foreground: color(style.fg),
+            background: color(style.bg),
+            font_style: tui_modifiers_to_syntect(style.add_modifier),
+        }
+    }
+}
+
+/// Returns `true` if the theme has a dark background. This can be used to pick a matching color
+/// palette for the UI surrounding a code block.
+///
+/// Themes without a background, or that use the terminal's default background, are assumed to
+/// be dark. For ANSI themes, only the gray and white palette entries are considered light.
+pub fn theme_is_dark(theme: &syntect::highlighting::Theme) -> bool {
+    match theme.settings.background.and_then(RunColor::from_syntect) {
+        Some(RunColor::Rgb(r, g, b)) => {
+            relative_luminance(syntect::highlighting::Color { r, g, b, a: 0xFF }) < DARK_LUMINANCE
+        }
+        Some(RunColor::Indexed(index)) => !matches!(index, 0x07 | 0x0F),
+        None => true,
+    }
+}
+
+#[cfg(feature = "ratatui")]
+pub(crate) fn ansi_color_to_tui(index: u8) -> ratatui_core::style::Color {
+    match index {
+        0x00 => ratatui_core::style::Color::Black,
+        0x01 => ratatui_core::style::Color::Red,
+        0x02 => ratatui_core::style::Color::Green,
+        0x03 => ratatui_core::style::Color::Yellow,
+        0x04 => ratatui_core::style::Color::Blue,
+        0x05 => ratatui_core::style::Color::Magenta,
+        0x06 => ratatui_core::style::Color::Cyan,
+        0x07 => ratatui_core::style::Color::Gray,
+        0x08 => ratatui_core::style::Color::DarkGray,
+        0x09 => ratatui_core::style::Color::LightRed,
+        0x0A => ratatui_core::style::Color::LightGreen,
+        0x0B => ratatui_core::style::Color::LightYellow,
+        0x0C => ratatui_core::style::Color::LightBlue,
+        0x0D => ratatui_core::style::Color::LightMagenta,
+        0x0E => ratatui_core::style::Color::LightCyan,
+        0x0F => ratatui_core::style::Color::White,
+        c => ratatui_core::style::Color::Indexed(c),
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    channel(r1, r2) + channel(g1, g2) + channel(b1, b2)
+}
+
+// Finds the nearest entry in the color cube or grayscale ramp. The first 16 entries are skipped
+// since their values vary between terminals.
+fn rgb_to_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+    let gray = (232..=255)
+        .min_by_key(|&index| distance(RunColor::Indexed(index).to_rgb(), rgb))
+        .unwrap_or(232);
+    if distance(RunColor::Indexed(cube).to_rgb(), rgb)
+        <= distance(RunColor::Indexed(gray).to_rgb(), rgb)
+    {
+        cube
+    } else {
+        gray
+    }
 }
 
+fn rgb_to_ansi16(rgb: (u8, u8, u8)) -> u8 {
+    (0..ANSI16_PALETTE.len())
+        .min_by_key(|&i| distance(ANSI16_PALETTE[i], rgb))
+        .unwrap_or(0) as u8
+}
+
+#[cfg(feature = "ratatui")]
 fn syntect_modifiers_to_tui(
     style: &syntect::highlighting::FontStyle,
 ) -> ratatui_core::style::Modifier {
@@ -103,3 +391,62 @@ fn syntect_modifiers_to_tui(
     }
     modifier
 }
+
+#[cfg(feature = "ratatui")]
+fn tui_modifiers_to_syntect(
+    modifier: ratatui_core::style::Modifier,
+) -> syntect::highlighting::FontStyle {
+    let mut style = syntect::highlighting::FontStyle::empty();
+    if modifier.intersects(ratatui_core::style::Modifier::BOLD) {
+        style |= syntect::highlighting::FontStyle::BOLD;
+    }
+    if modifier.intersects(ratatui_core::style::Modifier::ITALIC) {
+        style |= syntect::highlighting::FontStyle::ITALIC;
+    }
+    if modifier.intersects(ratatui_core::style::Modifier::UNDERLINED) {
+        style |= syntect::highlighting::FontStyle::UNDERLINE;
+    }
+    style
+}
+
+// Contrast ratios below this are treated as "near the background".
+#[cfg(feature = "ratatui")]
+const LOW_CONTRAST_RATIO: f32 = 2.0;
+
+// Backgrounds below this luminance contrast more with white text than black text.
+const DARK_LUMINANCE: f32 = 0.179;
+
+#[cfg(feature = "ratatui")]
+fn is_low_contrast(
+    foreground: syntect::highlighting::Color,
+    background: syntect::highlighting::Color,
+) -> bool {
+    // Alpha values of 0 and 1 are used to encode ANSI and default colors, so only true RGB values
+    // can be compared.
+    if foreground.a <= 1 || background.a <= 1 {
+        return false;
+    }
+    if foreground.a < 0x80 {
+        return true;
+    }
+    let foreground = relative_luminance(foreground);
+    let background = relative_luminance(background);
+    let (lighter, darker) = if foreground > background {
+        (foreground, background)
+    } else {
+        (background, foreground)
+    };
+    (lighter + 0.05) / (darker + 0.05) < LOW_CONTRAST_RATIO
+}
+
+fn relative_luminance(color: syntect::highlighting::Color) -> f32 {
+    let channel = |c: u8| {
+        let c = f32::from(c) / 255.0;
+        if c <= 0.039_28 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+}
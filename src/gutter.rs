@@ -0,0 +1,25 @@
+//! Built-in templates for use with
+//! [`Highlighter::gutter_template`](crate::Highlighter::gutter_template).
+
+use ratatui_core::style::Style;
+use ratatui_core::text::Span;
+
+/// Renders right-aligned line numbers with no separator.
+pub fn line_numbers_only() -> impl Fn(usize, Style) -> Vec<Span<'static>> + Send + Sync + 'static {
+    |line_number, style| vec![Span::styled(format!("{line_number:>3} "), style)]
+}
+
+/// Renders right-aligned line numbers followed by an arrow.
+pub fn numbers_with_arrow() -> impl Fn(usize, Style) -> Vec<Span<'static>> + Send + Sync + 'static {
+    |line_number, style| {
+        vec![
+            Span::styled(format!("{line_number:>3} "), style),
+            Span::styled("→ ", style),
+        ]
+    }
+}
+
+/// Renders a vertical bar with no line numbers.
+pub fn bar() -> impl Fn(usize, Style) -> Vec<Span<'static>> + Send + Sync + 'static {
+    |_, style| vec![Span::styled("▎ ", style)]
+}
@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, BufRead};
+
+use ratatui_core::text::Line;
+
+use crate::LineHighlighter;
+
+/// An iterator that reads and highlights one line at a time from a [`BufRead`] source.
+///
+/// Nothing is read until the next line is requested, so a consumer can render lines as they
+/// arrive and stop early without reading the rest of the input. Iteration ends after the first
+/// error. Create one using [`Highlighter::highlight_iter`](crate::Highlighter::highlight_iter).
+pub struct HighlightIter<'a, R> {
+    reader: R,
+    highlighter: LineHighlighter<'a>,
+    line: String,
+    pending: VecDeque<Line<'static>>,
+    done: bool,
+}
+
+impl<R> Debug for HighlightIter<'_, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HighlightIter")
+            .field("highlighter", &self.highlighter)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, R> HighlightIter<'a, R> {
+    pub(crate) fn new(reader: R, highlighter: LineHighlighter<'a>) -> Self {
+        Self {
+            reader,
+            highlighter,
+            line: String::new(),
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl<R> HighlightIter<'_, R> {
+    // Returns the next item if it's available without reading, or None if more input is needed.
+    fn next_pending(&mut self) -> Option<Option<Result<Line<'static>, crate::Error>>> {
+        if let Some(line) = self.pending.pop_front() {
+            return Some(Some(Ok(line)));
+        }
+        if self.done {
+            return Some(None);
+        }
+        self.line.clear();
+        None
+    }
+
+    // Highlights the line that was just read into the buffer. Returns None if the line only
+    // produced pending rows, or was hidden by a fold.
+    fn handle_read(
+        &mut self,
+        read: io::Result<usize>,
+    ) -> Option<Result<Line<'static>, crate::Error>> {
+        match read {
+            Ok(0) => {
+                self.done = true;
+                self.highlighter.finish().transpose()
+            }
+            Ok(_) => match self.highlighter.push_rows(&self.line) {
+                Ok(rows) => {
+                    self.pending.extend(rows);
+                    None
+                }
+                Err(e) => {
+                    self.done = true;
+                    Some(Err(e))
+                }
+            },
+            Err(e) => {
+                self.done = true;
+                Some(Err(crate::Error::Read(e)))
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn next_async(&mut self) -> Option<Result<Line<'static>, crate::Error>>
+    where
+        R: tokio::io::AsyncBufRead + Unpin,
+    {
+        use tokio::io::AsyncBufReadExt;
+
+        loop {
+            if let Some(next) = self.next_pending() {
+                return next;
+            }
+            // Mirrors Highlighter::read_line, which can't be shared since the reads are async.
+            let read = if self.highlighter.highlighter().is_lossy_utf8() {
+                let mut bytes = Vec::new();
+                let read = self.reader.read_until(b'\n', &mut bytes).await;
+                self.line.push_str(&String::from_utf8_lossy(&bytes));
+                read
+            } else {
+                self.reader.read_line(&mut self.line).await
+            };
+            if let Some(next) = self.handle_read(read) {
+                return Some(next);
+            }
+        }
+    }
+}
+
+impl<R> Iterator for HighlightIter<'_, R>
+where
+    R: BufRead,
+{
+    type Item = Result<Line<'static>, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.next_pending() {
+                return next;
+            }
+            let read = self
+                .highlighter
+                .highlighter()
+                .read_line(&mut self.reader, &mut self.line);
+            if let Some(next) = self.handle_read(read) {
+                return Some(next);
+            }
+        }
+    }
+}
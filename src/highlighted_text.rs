@@ -0,0 +1,393 @@
+use std::ops::Deref;
+
+use ratatui_core::buffer::Buffer;
+use ratatui_core::layout::Rect;
+use ratatui_core::style::{Color, Style, Stylize};
+use ratatui_core::text::{Line, Span, Text};
+use ratatui_core::widgets::{StatefulWidget, Widget};
+use ratatui_widgets::list::ListItem;
+use ratatui_widgets::paragraph::Paragraph;
+
+use crate::spans;
+
+const ELLIPSIS: &str = "…";
+
+/// Highlighted [`Text`] along with the background color of the code block.
+///
+/// When rendered, the background is applied to the whole area so it matches the highlighted
+/// content.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HighlightedText<'a> {
+    text: Text<'a>,
+    background: Option<Color>,
+    version: u64,
+    east_asian_width: bool,
+    truncate_marker: Option<String>,
+    truncate_marker_style: Style,
+    scroll: (u16, u16),
+}
+
+impl<'a> HighlightedText<'a> {
+    /// Creates a new [`HighlightedText`] from highlighted [`Text`] and the background color of the
+    /// code block, typically from [`Highlighter::get_background_color`].
+    ///
+    /// [`Highlighter::get_background_color`]: crate::Highlighter::get_background_color
+    pub fn new(text: Text<'a>, background: Option<Color>) -> Self {
+        Self {
+            text,
+            background,
+            version: 0,
+            east_asian_width: false,
+            truncate_marker: Some(ELLIPSIS.to_string()),
+            truncate_marker_style: Style::new(),
+            scroll: (0, 0),
+        }
+    }
+
+    /// Creates a new [`HighlightedText`] from [`Text`] without a background color, so the area
+    /// behind the code block is left as-is when rendered.
+    pub fn from_text(text: Text<'a>) -> Self {
+        Self::new(text, None)
+    }
+
+    /// Counts characters with an ambiguous East Asian width as two columns instead of one when
+    /// truncating or padding lines. This should match the
+    /// [`east_asian_width`](crate::Highlighter::east_asian_width) setting used to highlight the
+    /// text.
+    pub fn with_east_asian_width(mut self, east_asian_width: bool) -> Self {
+        self.east_asian_width = east_asian_width;
+        self
+    }
+
+    /// Sets the marker that replaces the end of lines cut off by
+    /// [`into_paragraph_truncated`](Self::into_paragraph_truncated). This should match the
+    /// [`truncate_marker`](crate::Highlighter::truncate_marker) setting used to highlight the
+    /// text. `…` is used by default. Set this to `None` to cut lines off without a marker.
+    pub fn with_truncate_marker(mut self, marker: Option<String>) -> Self {
+        self.truncate_marker = marker;
+        self
+    }
+
+    /// Sets the style patched onto the marker at the end of truncated lines. Text from
+    /// [`Highlighter`](crate::Highlighter) widgets uses the line number color, like
+    /// [`highlight_lines_width`](crate::Highlighter::highlight_lines_width). By default, the marker
+    /// keeps the style of the text it replaces.
+    pub fn with_truncate_marker_style(mut self, style: Style) -> Self {
+        self.truncate_marker_style = style;
+        self
+    }
+
+    /// Scrolls the content by the given `(y, x)` offset when rendered, like
+    /// [`Paragraph::scroll`]. The background still fills the whole area.
+    pub fn scroll(mut self, offset: (u16, u16)) -> Self {
+        self.scroll = offset;
+        self
+    }
+
+    /// Tags the [`HighlightedText`] with the version of the source it was highlighted from.
+    ///
+    /// Editors can bump a version counter on every edit and compare it using
+    /// [`is_stale`](Self::is_stale) to decide whether a cached result can be reused, which is
+    /// cheaper than hashing or comparing the source.
+    pub fn with_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Returns the version of the source this was highlighted from. This is `0` unless set with
+    /// [`with_version`](Self::with_version).
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns `true` if this was highlighted from a different version of the source than
+    /// `version`, meaning it needs to be highlighted again.
+    pub fn is_stale(&self, version: u64) -> bool {
+        self.version != version
+    }
+
+    pub(crate) fn push_line(&mut self, line: Line<'a>) {
+        self.text.lines.push(line);
+    }
+
+    /// Returns the highlighted [`Text`].
+    pub fn text(&self) -> &Text<'a> {
+        &self.text
+    }
+
+    /// Consumes the [`HighlightedText`] and returns the inner [`Text`].
+    pub fn into_text(self) -> Text<'a> {
+        self.text
+    }
+
+    /// Returns the number of rendered lines. When the text was highlighted with
+    /// [`wrap`](crate::Highlighter::wrap) enabled, each wrapped row counts as a separate line.
+    pub fn line_count(&self) -> usize {
+        self.text.lines.len()
+    }
+
+    /// Returns the display width of the widest line in columns, including the gutter. This is
+    /// useful for sizing a scrollbar or a surrounding block.
+    pub fn max_width(&self) -> usize {
+        self.text
+            .lines
+            .iter()
+            .map(|line| spans::spans_width(&line.spans, self.east_asian_width))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the background color of the code block, if one is set.
+    pub fn background(&self) -> Option<Color> {
+        self.background
+    }
+
+    /// Replaces the background color of the code block without re-highlighting the source.
+    ///
+    /// Every line and span that used the previous background is updated to use `background`.
+    /// Foreground colors and any other backgrounds, such as highlighted ranges, are left unchanged.
+    pub fn with_background(mut self, background: Option<Color>) -> Self {
+        let previous = self.background;
+        for line in &mut self.text.lines {
+            if line.style.bg == previous {
+                line.style.bg = background;
+            }
+            for span in &mut line.spans {
+                if span.style.bg == previous {
+                    span.style.bg = background;
+                }
+            }
+        }
+        self.background = background;
+        self
+    }
+
+    /// Applies `f` to every span, passing the line index and the span's index within the line.
+    /// This can be used to post-process the highlighted output, such as redacting secrets or
+    /// adding custom emphasis. Note that the gutter is made up of spans as well, so span indices
+    /// include any gutter spans.
+    pub fn map_spans<F>(mut self, mut f: F) -> Self
+    where
+        F: FnMut(usize, usize, Span<'a>) -> Span<'a>,
+    {
+        for (line_index, line) in self.text.lines.iter_mut().enumerate() {
+            line.spans = std::mem::take(&mut line.spans)
+                .into_iter()
+                .enumerate()
+                .map(|(span_index, span)| f(line_index, span_index, span))
+                .collect();
+        }
+        self
+    }
+
+    /// Converts the [`HighlightedText`] into a [`Paragraph`] like
+    /// [`into_paragraph`](Self::into_paragraph), but truncates each line to fit within `width`
+    /// columns instead of relying on wrapping. Truncated lines end with the
+    /// [`truncate_marker`](Self::with_truncate_marker).
+    pub fn into_paragraph_truncated(mut self, width: u16) -> Paragraph<'a> {
+        let width = usize::from(width);
+        let marker = self.truncate_marker.as_deref().unwrap_or_default();
+        for line in &mut self.text.lines {
+            spans::truncate_line(
+                line,
+                width,
+                marker,
+                self.truncate_marker_style,
+                self.east_asian_width,
+            );
+        }
+        self.into_paragraph()
+    }
+
+    /// Converts each line into a [`ListItem`], preserving its styles and gutter. This lets a
+    /// [`List`](ratatui_widgets::list::List) provide selection and scrolling over the code.
+    pub fn into_list_items(self) -> Vec<ListItem<'a>> {
+        self.text.lines.into_iter().map(ListItem::new).collect()
+    }
+
+    /// Converts the [`HighlightedText`] into a [`Paragraph`] with the background color applied.
+    pub fn into_paragraph(self) -> Paragraph<'a> {
+        let paragraph = Paragraph::new(self.text).scroll(self.scroll);
+        if let Some(bg) = self.background {
+            paragraph.bg(bg)
+        } else {
+            paragraph
+        }
+    }
+
+    // Creates a copy that borrows the content of each span, so rendering by reference doesn't copy
+    // the text.
+    fn borrowed(&self) -> HighlightedText<'_> {
+        let text = Text {
+            lines: self
+                .text
+                .lines
+                .iter()
+                .map(|line| Line {
+                    spans: line
+                        .spans
+                        .iter()
+                        .map(|span| Span::styled(span.content.as_ref(), span.style))
+                        .collect(),
+                    style: line.style,
+                    alignment: line.alignment,
+                })
+                .collect(),
+            style: self.text.style,
+            alignment: self.text.alignment,
+        };
+        HighlightedText {
+            text,
+            background: self.background,
+            version: self.version,
+            east_asian_width: self.east_asian_width,
+            truncate_marker: self.truncate_marker.clone(),
+            truncate_marker_style: self.truncate_marker_style,
+            scroll: self.scroll,
+        }
+    }
+}
+
+impl<'a> Deref for HighlightedText<'a> {
+    type Target = Text<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.text
+    }
+}
+
+impl Widget for HighlightedText<'_> {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        // Rows scrolled off to the left still count towards the width that needs to be filled.
+        fill_rows(
+            &mut self.text,
+            self.background,
+            area.width.saturating_add(self.scroll.1),
+            self.east_asian_width,
+        );
+        self.into_paragraph().render(area, buf);
+    }
+}
+
+impl Widget for &HighlightedText<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Widget::render(self.borrowed(), area, buf);
+    }
+}
+
+impl StatefulWidget for HighlightedText<'_> {
+    type State = ScrollState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        state.update(area.height, self.text.lines.len());
+        Widget::render(self.scroll(state.offset), area, buf);
+    }
+}
+
+impl StatefulWidget for &HighlightedText<'_> {
+    type State = ScrollState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        StatefulWidget::render(self.borrowed(), area, buf, state);
+    }
+}
+
+/// The scroll position of a [`HighlightedText`] rendered as a [`StatefulWidget`].
+///
+/// The size of the viewport and content is recorded on every render, so the vertical offset stays
+/// within the content and paging moves by a full viewport. Scrolling before the first render is
+/// limited to the content once it's rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ScrollState {
+    offset: (u16, u16),
+    viewport_height: u16,
+    content_height: u16,
+}
+
+impl ScrollState {
+    /// Creates a new [`ScrollState`] scrolled to the top.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current `(y, x)` offset.
+    pub fn offset(&self) -> (u16, u16) {
+        self.offset
+    }
+
+    /// Sets the `(y, x)` offset. The vertical offset is limited to the content on the next render.
+    pub fn set_offset(&mut self, offset: (u16, u16)) {
+        self.offset = offset;
+    }
+
+    /// Scrolls up by `lines`.
+    pub fn scroll_up(&mut self, lines: u16) {
+        self.offset.0 = self.offset.0.saturating_sub(lines);
+    }
+
+    /// Scrolls down by `lines`, stopping once the last line reaches the bottom of the viewport.
+    pub fn scroll_down(&mut self, lines: u16) {
+        self.offset.0 = self.offset.0.saturating_add(lines).min(self.max_offset());
+    }
+
+    /// Scrolls left by `columns`.
+    pub fn scroll_left(&mut self, columns: u16) {
+        self.offset.1 = self.offset.1.saturating_sub(columns);
+    }
+
+    /// Scrolls right by `columns`.
+    pub fn scroll_right(&mut self, columns: u16) {
+        self.offset.1 = self.offset.1.saturating_add(columns);
+    }
+
+    /// Scrolls up by the height of the viewport.
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.viewport_height.max(1));
+    }
+
+    /// Scrolls down by the height of the viewport.
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.viewport_height.max(1));
+    }
+
+    /// Scrolls to the first line.
+    pub fn scroll_to_top(&mut self) {
+        self.offset.0 = 0;
+    }
+
+    /// Scrolls to the last page.
+    pub fn scroll_to_bottom(&mut self) {
+        self.offset.0 = self.max_offset();
+    }
+
+    // Before the first render the sizes are unknown, so the offset is left unbounded until
+    // `update` limits it to the content.
+    fn max_offset(&self) -> u16 {
+        if self.viewport_height == 0 && self.content_height == 0 {
+            return u16::MAX;
+        }
+        self.content_height.saturating_sub(self.viewport_height)
+    }
+
+    fn update(&mut self, viewport_height: u16, content_height: usize) {
+        self.viewport_height = viewport_height;
+        self.content_height = u16::try_from(content_height).unwrap_or(u16::MAX);
+        self.offset.0 = self.offset.0.min(self.max_offset());
+    }
+}
+
+// Pads rows styled differently from the code block, such as the current line or highlighted
+// ranges, so their background covers the full width even when the line is short or empty.
+fn fill_rows(text: &mut Text<'_>, background: Option<Color>, width: u16, east_asian: bool) {
+    for line in &mut text.lines {
+        if line.style.bg == background {
+            continue;
+        }
+        let padding =
+            usize::from(width).saturating_sub(spans::spans_width(&line.spans, east_asian));
+        if padding > 0 {
+            line.spans
+                .push(Span::styled(" ".repeat(padding), line.style));
+        }
+    }
+}
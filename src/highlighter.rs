@@ -1,21 +1,263 @@
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
 
-use ratatui_core::style::{Color, Style, Stylize};
+use glob::Pattern;
+use ratatui_core::layout::Alignment;
+use ratatui_core::style::{Color, Modifier, Style, Stylize};
 use ratatui_core::text::{Line, Span, Text};
+use ratatui_widgets::block::Block;
+use ratatui_widgets::borders::BorderType;
+use ratatui_widgets::paragraph::Paragraph;
 pub use syntect;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::Theme;
-use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::highlighting::{FontStyle, ScopeSelectors, Theme};
+use syntect::parsing::{ScopeStack, SyntaxReference, SyntaxSet};
 #[cfg(feature = "termprofile")]
 use termprofile::TermProfile;
 
-use crate::Converter;
+use crate::render::{strip_line_ending, with_lf, with_newline};
+use crate::{
+    ColorDepth, CompilerError, Converter, HighlightIter, HighlightedText, LineCache,
+    LineHighlighter, Transcript, spans,
+};
 
-type GutterFn = dyn Fn(usize, Style) -> Vec<Span<'static>> + Send + Sync;
+type GutterFn = dyn Fn(GutterContext) -> Vec<Span<'static>> + Send + Sync;
+type SeparatorFn = dyn Fn(usize, LineFlags) -> Span<'static> + Send + Sync;
+
+/// Information about a line, used to customize how it's rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct LineFlags {
+    /// The line is the [`current_line`](Highlighter::current_line).
+    pub is_current: bool,
+    /// The line is inside a [`highlight_range`](Highlighter::highlight_range).
+    pub is_highlighted: bool,
+    /// The line is directly before or after a [`fold`](Highlighter::fold).
+    pub is_fold_boundary: bool,
+}
+
+/// Information about a line passed to a
+/// [`gutter_template_with_context`](Highlighter::gutter_template_with_context).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct GutterContext {
+    /// The 1-based line number.
+    pub line_number: usize,
+    /// The line number style, accounting for the theme and any overrides.
+    pub base_style: Style,
+    /// The line is inside a [`highlight_range`](Highlighter::highlight_range).
+    pub is_highlighted: bool,
+    /// The line is the [`current_line`](Highlighter::current_line).
+    pub is_current: bool,
+}
+
+/// The location of a match from [`search_highlight`](Highlighter::search_highlight).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SearchMatch {
+    /// The zero-based index of the line containing the match.
+    pub line: usize,
+    /// The byte range of the match within the line.
+    pub range: Range<usize>,
+}
+
+/// A token from [`highlight_lines_scoped`](Highlighter::highlight_lines_scoped) along with the
+/// syntax scopes that apply to it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScopedSpan {
+    /// The byte range of the token within the source line.
+    pub range: Range<usize>,
+    /// The scope stack of the token, from outermost to innermost, separated by spaces. This can
+    /// be used as a scope selector, such as `source.sql keyword.other.DML.sql`.
+    pub scope: String,
+}
+
+/// A URL found by [`find_links`](Highlighter::find_links).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Link {
+    /// The zero-based index of the line containing the URL.
+    pub line: usize,
+    /// The byte range of the URL within the line.
+    pub range: Range<usize>,
+    /// The URL itself.
+    pub url: String,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum SearchQuery {
+    Text(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl SearchQuery {
+    fn find(&self, text: &str, case_insensitive: bool) -> Vec<Range<usize>> {
+        match self {
+            Self::Text(query) => find_query(text, query, case_insensitive),
+            // Zero-width matches have nothing to highlight.
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect(),
+        }
+    }
+}
+
+// Regexes don't implement PartialEq, so they're compared by their pattern.
+impl PartialEq for SearchQuery {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Text(a), Self::Text(b)) => a == b,
+            #[cfg(feature = "regex")]
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            #[cfg(feature = "regex")]
+            _ => false,
+        }
+    }
+}
+
+// The position and length of a hunk in the old and new versions of a file, parsed from a header
+// like `@@ -1,4 +1,5 @@`.
+struct HunkHeader {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+}
+
+impl HunkHeader {
+    fn parse(line: &str) -> Option<Self> {
+        let ranges = line.strip_prefix("@@ ")?;
+        let (ranges, _) = ranges.split_once(" @@")?;
+        let (old, new) = ranges.split_once(' ')?;
+        // The length is omitted when it's 1.
+        let parse_range = |range: &str| -> Option<(usize, usize)> {
+            match range.split_once(',') {
+                Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1)),
+            }
+        };
+        let (old_start, old_len) = parse_range(old.strip_prefix('-')?)?;
+        let (new_start, new_len) = parse_range(new.strip_prefix('+')?)?;
+        Some(Self {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        })
+    }
+}
+
+// Fills in a theme setting with a default color, replacing the previous default if one was
+// already filled in.
+fn fill_default(
+    setting: &mut Option<syntect::highlighting::Color>,
+    color: syntect::highlighting::Color,
+    previous: Option<syntect::highlighting::Color>,
+) {
+    if setting.is_none() || *setting == previous {
+        *setting = Some(color);
+    }
+}
+
+// Highlights each line from `source` and collects the rows.
+fn collect_rows<'a, I>(
+    mut highlighter: LineHighlighter<'_>,
+    source: I,
+    count: usize,
+) -> Result<Text<'static>, crate::Error>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut source = source.peekable();
+    let mut formatted = Vec::with_capacity(count);
+    while let Some(line) = source.next() {
+        if line.is_empty() && source.peek().is_none() && highlighter.ends_with_newline() {
+            // The previous line already ended with a newline, so this empty line is only the
+            // remainder after it rather than a blank line in the source.
+            break;
+        }
+        formatted.extend(highlighter.push_rows(line)?);
+    }
+    formatted.extend(highlighter.finish()?);
+    Ok(Text::from_iter(formatted))
+}
+
+// Returns the number of items left in the iterator if it's known exactly.
+fn exact_len<I>(iter: &I) -> Option<usize>
+where
+    I: Iterator,
+{
+    match iter.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(lower),
+        _ => None,
+    }
+}
+
+/// Controls how lines that are wider than the available space are wrapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    /// Lines are not wrapped.
+    #[default]
+    None,
+    /// Lines are broken at the last character that fits.
+    Character,
+    /// Lines are broken after the last whitespace that fits. Words that are too long to fit on
+    /// their own row are broken between characters.
+    Word,
+}
+
+/// Controls the background of the code block, set with
+/// [`override_background`](Highlighter::override_background).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OverrideBackground {
+    /// The theme's background is used.
+    #[default]
+    Theme,
+    /// Every cell is painted with the given color. [`Color::Reset`] paints cells with the
+    /// terminal's default background, which some terminals draw as a solid block.
+    Color(Color),
+    /// The background is left unset, so whatever is already in the buffer shows through.
+    None,
+}
+
+impl From<Color> for OverrideBackground {
+    fn from(color: Color) -> Self {
+        Self::Color(color)
+    }
+}
+
+/// Describes how to find a syntax with [`resolve_syntax`](Highlighter::resolve_syntax).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxHint<'a> {
+    /// The name of the syntax, such as `Rust`.
+    Name(&'a str),
+    /// A file extension without the leading dot, such as `rs`. Full file names such as `Makefile`
+    /// are also matched.
+    Extension(&'a str),
+    /// The first line of the content, which is matched against patterns such as shebangs.
+    FirstLine(&'a str),
+    /// A token such as a markdown code block language tag. This matches names case
+    /// insensitively as well as extensions.
+    Token(&'a str),
+}
+
+#[derive(Clone, Debug)]
+struct ScopeStyle {
+    selectors: ScopeSelectors,
+    // Additional selectors that must also match for the style to apply.
+    filter: Option<ScopeSelectors>,
+    style: Style,
+}
 
 #[derive(Clone)]
 struct GutterTemplate(Arc<GutterFn>);
@@ -26,23 +268,82 @@ impl Debug for GutterTemplate {
     }
 }
 
+#[derive(Clone)]
+struct SeparatorTemplate(Arc<SeparatorFn>);
+
+impl Debug for SeparatorTemplate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("SeparatorTemplate(<fn>)")
+    }
+}
+
 /// A syntax highlighter that produces styled [`Text`](ratatui_core::text::Text) output.
 /// The output style can be changed using the configuration methods provided in this struct.
 #[derive(Clone, Debug)]
 pub struct Highlighter {
     theme: Theme,
-    override_background: Option<Color>,
+    theme_name: Option<String>,
+    override_background: OverrideBackground,
+    gutter_background: Option<Color>,
+    default_foreground: Option<Color>,
+    default_background: Option<Color>,
     line_number_style: Option<Style>,
     line_number_separator_style: Option<Style>,
+    current_line: Option<usize>,
+    current_line_style: Option<Style>,
+    current_line_separator_style: Option<Style>,
     gutter_template: Option<GutterTemplate>,
+    separator_template: Option<SeparatorTemplate>,
     line_numbers: bool,
+    relative_line_numbers: Option<usize>,
     line_number_padding: usize,
+    line_number_alignment: Alignment,
+    auto_line_number_width: bool,
+    gutter_width_bounds: Option<(usize, usize)>,
+    gutter_overflow_marker: char,
     line_number_separator: String,
     #[cfg(feature = "termprofile")]
     profile: TermProfile,
-    highlight_ranges: Vec<Range<usize>>,
+    highlight_ranges: Vec<(Range<usize>, Option<Style>)>,
     highlight_style: Style,
+    highlight_code_only: bool,
+    zebra_stripe: Option<(Style, Style)>,
+    error_style: Style,
+    diff_add_style: Style,
+    diff_remove_style: Style,
+    diff_hunk_style: Style,
+    scope_styles: Vec<ScopeStyle>,
+    max_spans_per_line: Option<usize>,
+    min_content_width: u16,
+    minimap_width: u16,
+    tab_width: usize,
+    trailing_whitespace_style: Option<Style>,
+    show_whitespace_on_blank: bool,
+    wrap: WrapMode,
+    wrap_width: u16,
+    east_asian_width: bool,
+    folds: Vec<(Range<usize>, String)>,
+    search_query: Option<SearchQuery>,
+    search_style: Style,
+    link_style: Option<Style>,
+    search_case_insensitive: bool,
+    current_match: Option<(usize, Style)>,
+    bracket_match: Option<((usize, usize), Style)>,
+    selection: Option<((usize, usize), (usize, usize))>,
+    highlight_spans: Vec<(usize, Range<usize>, Style)>,
+    selection_style: Option<Style>,
+    with_scopes: bool,
+    truncate_marker: Option<String>,
+    language_map: Vec<(Pattern, String)>,
+    fallback_syntax: Option<SyntaxReference>,
+    syntax_themes: Vec<(String, Theme)>,
+    async_threshold: usize,
+    plain: bool,
+    keep_final_newline: bool,
+    lossy_utf8: bool,
+    preserve_line_endings: bool,
     converter: Converter,
+    monochrome: bool,
 }
 
 impl Highlighter {
@@ -50,19 +351,120 @@ impl Highlighter {
     pub fn new(theme: Theme) -> Self {
         Self {
             theme,
-            override_background: None,
+            theme_name: None,
+            override_background: OverrideBackground::Theme,
+            gutter_background: None,
+            default_foreground: None,
+            default_background: None,
             line_number_style: None,
             line_number_separator_style: None,
+            current_line: None,
+            current_line_style: None,
+            current_line_separator_style: None,
             gutter_template: None,
+            separator_template: None,
             line_numbers: true,
+            relative_line_numbers: None,
             line_number_padding: 4,
+            line_number_alignment: Alignment::Right,
+            auto_line_number_width: false,
+            gutter_width_bounds: None,
+            gutter_overflow_marker: '…',
             line_number_separator: "│".to_string(),
             #[cfg(feature = "termprofile")]
             profile: TermProfile::TrueColor,
             highlight_ranges: Vec::new(),
             highlight_style: Style::new().bg(Color::Yellow),
+            highlight_code_only: false,
+            zebra_stripe: None,
+            error_style: Style::new().fg(Color::Red),
+            diff_add_style: Style::new().bg(Color::Indexed(22)),
+            diff_remove_style: Style::new().bg(Color::Indexed(52)),
+            diff_hunk_style: Style::new().fg(Color::Cyan),
+            scope_styles: Vec::new(),
+            max_spans_per_line: None,
+            min_content_width: 0,
+            minimap_width: 8,
+            tab_width: 4,
+            trailing_whitespace_style: None,
+            show_whitespace_on_blank: false,
+            wrap: WrapMode::None,
+            wrap_width: 0,
+            east_asian_width: false,
+            folds: Vec::new(),
+            search_query: None,
+            search_style: Style::new(),
+            link_style: None,
+            search_case_insensitive: false,
+            current_match: None,
+            bracket_match: None,
+            selection: None,
+            highlight_spans: Vec::new(),
+            selection_style: None,
+            with_scopes: false,
+            truncate_marker: Some("…".to_string()),
+            language_map: Vec::new(),
+            fallback_syntax: None,
+            syntax_themes: Vec::new(),
+            async_threshold: 256 * 1024,
+            plain: false,
+            keep_final_newline: false,
+            lossy_utf8: false,
+            preserve_line_endings: false,
             converter: Converter::new(),
+            monochrome: false,
+        }
+    }
+
+    /// Creates a new [`Highlighter`] with the given [`Theme`], remembering the name it was loaded
+    /// by so it can be shown to users with [`theme_name`](Self::theme_name).
+    pub fn new_named<N>(name: N, theme: Theme) -> Self
+    where
+        N: Into<String>,
+    {
+        let mut this = Self::new(theme);
+        this.theme_name = Some(name.into());
+        this
+    }
+
+    /// Creates a new [`Highlighter`] with a theme read from the contents of a `.tmTheme` file.
+    ///
+    /// Returns [`Error::Read`](crate::Error::Read) if the reader fails, or
+    /// [`Error::Theme`](crate::Error::Theme) if the theme can't be parsed.
+    #[cfg(feature = "plist-load")]
+    pub fn from_tm_theme_reader<R>(mut reader: R) -> Result<Self, crate::Error>
+    where
+        R: io::Read,
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(crate::Error::Read)?;
+        Ok(Self::new(crate::load_theme_from_tm_theme(&bytes)?))
+    }
+
+    /// Creates a [`Highlighter`] that skips syntax highlighting. Code is rendered in the theme's
+    /// foreground color, while the gutter, background, and line-level features such as
+    /// [`highlight_range`](Self::highlight_range) still apply. This is useful for content like
+    /// config or data files where highlighting adds noise.
+    pub fn plain(theme: Theme) -> Self {
+        let mut this = Self::new(theme);
+        this.plain = true;
+        this
+    }
+
+    /// Creates a new [`Highlighter`], returning an error if the theme is missing a foreground,
+    /// a background, or any scope rules. This is useful for rejecting broken user-supplied themes
+    /// early instead of rendering everything unstyled.
+    pub fn try_new(theme: Theme) -> Result<Self, crate::Error> {
+        if theme.settings.foreground.is_none() {
+            return Err(crate::Error::InvalidTheme("missing foreground color"));
+        }
+        if theme.settings.background.is_none() {
+            return Err(crate::Error::InvalidTheme("missing background color"));
         }
+        if theme.scopes.is_empty() {
+            return Err(crate::Error::InvalidTheme("no scope rules"));
+        }
+        Ok(Self::new(theme))
     }
 
     /// Creates a new [`Highlighter`] with the given [`Theme`] and [`TermProfile`]. See the
@@ -77,13 +479,68 @@ impl Highlighter {
     }
 
     /// Override the background with a different color.
-    /// Set this to [`Color::Reset`] to disable the background color.
-    pub fn override_background<C>(mut self, background: C) -> Self
+    /// Set this to [`OverrideBackground::None`] to leave the background untouched, so the code
+    /// block is drawn on top of whatever is already in the buffer, or to
+    /// [`OverrideBackground::Theme`] to go back to the theme's background.
+    pub fn override_background<B>(mut self, background: B) -> Self
+    where
+        B: Into<OverrideBackground>,
+    {
+        let background = match background.into() {
+            OverrideBackground::Color(color) => {
+                OverrideBackground::Color(self.adapt_color(color).unwrap_or(Color::Reset))
+            }
+            background => background,
+        };
+        self.override_background = background;
+        self
+    }
+
+    /// Set the background color of the gutter, which covers the line numbers, the separator, and
+    /// the padding around them. This is independent of the code background set with
+    /// [`override_background`](Self::override_background). By default, the gutter uses a slightly
+    /// darker shade of the code background.
+    pub fn gutter_background<C>(mut self, background: C) -> Self
+    where
+        C: Into<Color>,
+    {
+        self.gutter_background = Some(self.adapt_color(background.into()).unwrap_or(Color::Reset));
+        self
+    }
+
+    /// Set the foreground color to use if the theme doesn't define one. This is also used for the
+    /// line numbers if the theme doesn't set a gutter color, instead of dark gray. Minimal themes
+    /// often leave these settings out, which otherwise renders poorly on light terminals.
+    ///
+    /// This applies to the primary theme and any themes set with
+    /// [`theme_for`](Self::theme_for).
+    pub fn default_foreground<C>(mut self, foreground: C) -> Self
+    where
+        C: Into<Color>,
+    {
+        let foreground = foreground.into();
+        let previous = self.default_foreground.replace(foreground);
+        let color = self.converter.tui_color_to_syntect(foreground);
+        let previous = previous.map(|previous| self.converter.tui_color_to_syntect(previous));
+        for theme in self.themes_mut() {
+            fill_default(&mut theme.settings.foreground, color, previous);
+        }
+        self
+    }
+
+    /// Set the background color to use if the theme doesn't define one, like
+    /// [`default_foreground`](Self::default_foreground).
+    pub fn default_background<C>(mut self, background: C) -> Self
     where
         C: Into<Color>,
     {
         let background = background.into();
-        self.override_background = Some(self.adapt_color(background).unwrap_or(Color::Reset));
+        let previous = self.default_background.replace(background);
+        let color = self.converter.tui_color_to_syntect(background);
+        let previous = previous.map(|previous| self.converter.tui_color_to_syntect(previous));
+        for theme in self.themes_mut() {
+            fill_default(&mut theme.settings.background, color, previous);
+        }
         self
     }
 
@@ -93,12 +550,60 @@ impl Highlighter {
         self
     }
 
+    /// Show line numbers relative to the zero-based `current` line, like an editor's hybrid line
+    /// number mode. Every other line shows its distance from `current`, while `current` itself
+    /// shows its absolute line number.
+    pub fn relative_line_numbers(mut self, current: usize) -> Self {
+        self.relative_line_numbers = Some(current);
+        self
+    }
+
+    /// Set the marker that replaces the end of lines that are cut off by
+    /// [`highlight_lines_width`](Self::highlight_lines_width). The marker is drawn in the gutter's
+    /// foreground color on top of the style of the last visible token. `…` is used by default.
+    /// Set this to `None` to cut lines off without a marker.
+    pub fn truncate_marker(mut self, marker: Option<String>) -> Self {
+        self.truncate_marker = marker;
+        self
+    }
+
+    /// Keep track of the syntax scopes of each token so they can be returned from
+    /// [`highlight_lines_scoped`](Self::highlight_lines_scoped). Disabled by default, since
+    /// building the scope names requires extra work for every token.
+    pub fn with_scopes(mut self, with_scopes: bool) -> Self {
+        self.with_scopes = with_scopes;
+        self
+    }
+
     /// Set the padding between the line number section and the rest of the code.
     pub fn line_number_padding(mut self, padding: usize) -> Self {
         self.line_number_padding = padding;
         self
     }
 
+    /// Size the [`line_number_padding`] to fit the largest line number when the number of lines
+    /// is known up front, such as when highlighting a slice with
+    /// [`highlight_lines`](Self::highlight_lines). The padding leaves room for the digits of the
+    /// last line number plus the usual space on either side.
+    ///
+    /// Readers, files, and iterators that don't report an exact length fall back to the fixed
+    /// padding.
+    ///
+    /// [`line_number_padding`]: Self::line_number_padding
+    pub fn auto_line_number_width(mut self, auto_line_number_width: bool) -> Self {
+        self.auto_line_number_width = auto_line_number_width;
+        self
+    }
+
+    /// Set the alignment of line numbers within the [`line_number_padding`]. Line numbers are
+    /// right-aligned by default.
+    ///
+    /// [`line_number_padding`]: Self::line_number_padding
+    pub fn line_number_align(mut self, alignment: Alignment) -> Self {
+        self.line_number_alignment = alignment;
+        self
+    }
+
     /// Set the [Style] for the line number section.
     pub fn line_number_style<S>(mut self, style: S) -> Self
     where
@@ -117,7 +622,60 @@ impl Highlighter {
         self
     }
 
-    /// Set the text used for the line number separator. `|` is used by default.
+    /// Set the current line, such as the line containing the cursor. Line indices are zero-based.
+    ///
+    /// The [`current_line_style`](Self::current_line_style) covers the full width of the line,
+    /// including the gutter. It's layered above the theme background and syntax colors, but
+    /// beneath [`highlight_range`](Self::highlight_range) and
+    /// [`search_highlight`](Self::search_highlight) styles, so the current line keeps any
+    /// attributes that those styles don't override.
+    pub fn current_line(mut self, line: Option<usize>) -> Self {
+        self.current_line = line;
+        self
+    }
+
+    /// Set the [Style] used for the [`current_line`](Self::current_line). By default, the theme's
+    /// line highlight color is used as the background.
+    pub fn current_line_style<S>(mut self, style: S) -> Self
+    where
+        S: Into<Style>,
+    {
+        self.current_line_style = Some(self.adapt_style(style.into()));
+        self
+    }
+
+    /// Set the [Style] for the line number separator on the
+    /// [`current_line`](Self::current_line). By default, the separator uses the theme's
+    /// foreground color so it stands out from the dimmer separators on other lines.
+    pub fn current_line_separator_style<S>(mut self, style: S) -> Self
+    where
+        S: Into<Style>,
+    {
+        self.current_line_separator_style = Some(self.adapt_style(style.into()));
+        self
+    }
+
+    /// Clamp the width of the gutter to between `min` and `max` columns. Line numbers that don't
+    /// fit are cut off from the left, keeping the last digits behind the
+    /// [`gutter_overflow_marker`](Self::gutter_overflow_marker). The separator and the spaces
+    /// around it are always kept.
+    ///
+    /// Gutters from a [`gutter_template`](Self::gutter_template) are padded with spaces or cut
+    /// off from the right to fit instead.
+    pub fn gutter_width_bounds(mut self, min: usize, max: usize) -> Self {
+        self.gutter_width_bounds = Some((min, max.max(min)));
+        self
+    }
+
+    /// Set the character shown in place of the leading digits of line numbers that are cut off
+    /// by [`gutter_width_bounds`](Self::gutter_width_bounds). `…` is used by default.
+    pub fn gutter_overflow_marker(mut self, marker: char) -> Self {
+        self.gutter_overflow_marker = marker;
+        self
+    }
+
+    /// Set the text used for the line number separator. `|` is used by default. If the separator
+    /// is empty, it's omitted along with the space that follows it.
     pub fn line_number_separator<T>(mut self, separator: T) -> Self
     where
         T: Into<String>,
@@ -126,9 +684,42 @@ impl Highlighter {
         self
     }
 
-    /// Highlight a specific range of code with a different style.
+    /// Highlight a specific range of code with a different style. The gutter keeps its own
+    /// background on highlighted lines.
     pub fn highlight_range(mut self, range: Range<usize>) -> Self {
-        self.highlight_ranges.push(range);
+        self.highlight_ranges.push((range, None));
+        self
+    }
+
+    /// Highlight a specific range of code with its own style instead of the
+    /// [`highlight_style`](Self::highlight_style), such as red for errors and yellow for warnings.
+    /// Where ranges overlap, the range added last wins.
+    pub fn highlight_range_styled(mut self, range: Range<usize>, style: Style) -> Self {
+        let style = self.adapt_style(style);
+        self.highlight_ranges.push((range, Some(style)));
+        self
+    }
+
+    /// Hide a range of lines, showing a single `summary` row in their place, such as
+    /// `… 42 lines …`. The summary is drawn in the line number style. If `summary` is empty, the
+    /// lines are hidden without a summary row. Line indices are zero-based. Hidden lines are still
+    /// parsed, so highlighting after the fold stays correct, and the gutter continues to show the
+    /// line numbers from the source rather than the position of the row.
+    ///
+    /// Multiple folds can be added in any order. When folds are nested, only the summary of the
+    /// outermost fold is shown.
+    ///
+    /// Folds apply to the methods that highlight a whole source at once, such as
+    /// [`highlight_lines`](Self::highlight_lines). Lines pushed to a [`LineHighlighter`] are always
+    /// returned.
+    pub fn fold<S>(mut self, range: Range<usize>, summary: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let index = self
+            .folds
+            .partition_point(|(fold, _)| fold.start <= range.start);
+        self.folds.insert(index, (range, summary.into()));
         self
     }
 
@@ -140,191 +731,2124 @@ impl Highlighter {
         self
     }
 
-    /// Set a template function to configure the gutter section. This is an alternative to using
-    /// [`line_number_style`], [`line_number_separator_style`], and [`line_number_padding`] if you
-    /// need more flexibility.
-    ///
-    /// [`line_number_style`]: Self::line_number_style
-    /// [`line_number_separator_style`]: Self::line_number_separator_style
-    /// [`line_number_padding`]: Self::line_number_padding
-    pub fn gutter_template<F>(mut self, template: F) -> Self
-    where
-        F: Fn(usize, Style) -> Vec<Span<'static>> + Send + Sync + 'static,
-    {
-        self.gutter_template = Some(GutterTemplate(Arc::new(template)));
+    /// Alternate the style of each row between `even` and `odd` based on the line number, which
+    /// makes long or dense code easier to scan. Stripes cover the whole row, including the
+    /// gutter. They're applied beneath the syntax colors and the
+    /// [`highlight_style`](Self::highlight_style), so they typically only set a background.
+    pub fn zebra_stripe(mut self, even: Style, odd: Style) -> Self {
+        self.zebra_stripe = Some((self.adapt_style(even), self.adapt_style(odd)));
         self
     }
 
-    /// Returns the configured background color, accounting for both the theme and any overrides.
-    /// This is useful if you want to render the code block into a larger section and you need the
-    /// background colors to match.
-    pub fn get_background_color(&self) -> Option<Color> {
-        if let Some(bg) = self.override_background {
-            Some(bg)
-        } else {
-            self.theme
-                .settings
-                .background
-                .and_then(|bg| self.converter.syntect_color_to_tui(bg))
-        }
+    /// Apply the [`highlight_style`](Self::highlight_style) to the code only, leaving the gutter in
+    /// its own style. The gutter always keeps its background, but by default the modifiers of the
+    /// highlight style, such as bold, also apply to the line numbers.
+    pub fn highlight_code_only(mut self, highlight_code_only: bool) -> Self {
+        self.highlight_code_only = highlight_code_only;
+        self
     }
 
-    /// Returns the configured line number style, accounting for both the theme and any overrides.
-    pub fn get_line_number_style(&self) -> Style {
-        if let Some(style) = self.line_number_style {
-            return style;
-        }
-        let mut style = Style::new();
-        if let Some(fg) = self
-            .theme
-            .settings
-            .gutter_foreground
-            .and_then(|fg| self.converter.syntect_color_to_tui(fg))
-        {
-            style = style.fg(fg);
-        } else {
-            style = style.dark_gray();
-        }
-        if let Some(bg) = self.get_background_color() {
-            style = style.bg(bg);
-        }
-        self.adapt_style(style)
+    /// Set the style used for error messages in
+    /// [`render_with_errors`](Self::render_with_errors). Red text is used by default.
+    pub fn error_style(mut self, style: Style) -> Self {
+        self.error_style = self.adapt_style(style);
+        self
     }
 
-    /// Highlights text from any [`io::Read`] source.
-    pub fn highlight_reader<R>(
-        &self,
-        reader: R,
-        syntax: &SyntaxReference,
-        syntaxes: &SyntaxSet,
-    ) -> Result<Text<'static>, crate::Error>
+    /// Set the style patched over added lines in [`highlight_diff`](Self::highlight_diff). A dark
+    /// green background is used by default.
+    pub fn diff_add_style(mut self, style: Style) -> Self {
+        self.diff_add_style = self.adapt_style(style);
+        self
+    }
+
+    /// Set the style patched over removed lines in [`highlight_diff`](Self::highlight_diff). A
+    /// dark red background is used by default.
+    pub fn diff_remove_style(mut self, style: Style) -> Self {
+        self.diff_remove_style = self.adapt_style(style);
+        self
+    }
+
+    /// Set the style used for hunk headers (`@@ ... @@`) in
+    /// [`highlight_diff`](Self::highlight_diff). Cyan text is used by default.
+    pub fn diff_hunk_style(mut self, style: Style) -> Self {
+        self.diff_hunk_style = self.adapt_style(style);
+        self
+    }
+
+    /// Set the background color for tokens matching the given scope selector, such as `string` or
+    /// `comment.line`. This is layered on top of the theme background and any
+    /// [`override_background`](Self::override_background). If multiple selectors match a token,
+    /// the most specific one wins.
+    ///
+    /// Scopes are only available when highlighting whole sources (such as with
+    /// [`highlight_lines`](Self::highlight_lines) or a [`LineHighlighter`]), so this has no effect
+    /// on [`highlight_line`](Self::highlight_line).
+    ///
+    /// Use [`try_scope_background`](Self::try_scope_background) for selectors that come from user
+    /// configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `selector` is not a valid scope selector.
+    pub fn scope_background<C>(self, selector: &str, background: C) -> Self
     where
-        R: io::Read,
+        C: Into<Color>,
     {
-        let mut reader = BufReader::new(reader);
-        let mut highlighter = HighlightLines::new(syntax, &self.theme);
-        let line_number_style = self.get_line_number_style();
-        let mut line = String::new();
-        let mut formatted = Vec::new();
-        let mut i = 0;
-        while reader.read_line(&mut line).map_err(crate::Error::Read)? > 0 {
-            let highlighted =
-                self.highlight_line(&line, &mut highlighter, i, line_number_style, syntaxes)?;
-            formatted.push(highlighted);
-            line.clear();
-            i += 1;
-        }
-        Ok(Text::from_iter(formatted))
+        self.try_scope_background(selector, background)
+            .expect("invalid scope selector")
     }
 
-    /// Highlights text from an iterator.
-    pub fn highlight_lines<'a, T>(
-        &self,
-        source: T,
-        syntax: &SyntaxReference,
-        syntaxes: &SyntaxSet,
-    ) -> Result<Text<'static>, crate::Error>
+    /// Set the background color for tokens matching the given scope selector like
+    /// [`scope_background`](Self::scope_background), but returns
+    /// [`Error::ScopeSelector`](crate::Error::ScopeSelector) if `selector` is not a valid scope
+    /// selector instead of panicking.
+    pub fn try_scope_background<C>(
+        self,
+        selector: &str,
+        background: C,
+    ) -> Result<Self, crate::Error>
     where
-        T: IntoIterator<Item = &'a str>,
+        C: Into<Color>,
     {
-        let mut highlighter = HighlightLines::new(syntax, &self.theme);
-        let line_number_style = self.get_line_number_style();
-        let formatted: Result<Vec<_>, crate::Error> = source
-            .into_iter()
-            .enumerate()
-            .map(|(i, line)| {
-                self.highlight_line(line, &mut highlighter, i, line_number_style, syntaxes)
-            })
-            .collect();
-        let formatted = formatted?;
-        Ok(Text::from_iter(formatted))
+        self.try_scope_style(selector, Style::new().bg(background.into()))
     }
 
-    /// Highlights a single line.
-    pub fn highlight_line(
-        &self,
-        line: &str,
-        highlighter: &mut HighlightLines,
-        line_number: usize,
-        line_number_style: Style,
-        syntaxes: &SyntaxSet,
-    ) -> Result<Line<'static>, crate::Error> {
-        let line: Cow<_> = if line.ends_with("\n") {
-            line.into()
-        } else {
-            (line.to_string() + "\n").into()
-        };
-        let regions = highlighter
-            .highlight_line(&line, syntaxes)
-            .map_err(crate::Error::Highlight)?;
-        Ok(self.to_line(&regions, line_number, line_number_style))
+    /// Patch `style` over tokens matching the given scope selector, such as `comment` or
+    /// `string.quoted`, without editing the theme. Overrides can be added for multiple selectors.
+    /// If more than one matches a token, they're applied in order of specificity so the most
+    /// specific selector wins.
+    ///
+    /// Like [`scope_background`](Self::scope_background), this has no effect on
+    /// [`highlight_line`](Self::highlight_line). Use
+    /// [`try_scope_override`](Self::try_scope_override) for selectors that come from user
+    /// configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `selector` is not a valid scope selector.
+    pub fn scope_override(self, selector: &str, style: Style) -> Self {
+        self.try_scope_override(selector, style)
+            .expect("invalid scope selector")
     }
 
-    fn get_initial_spans(
-        &self,
-        line_number: usize,
-        line_number_style: Style,
-    ) -> Vec<Span<'static>> {
+    /// Patch `style` over tokens matching the given scope selector like
+    /// [`scope_override`](Self::scope_override), but returns
+    /// [`Error::ScopeSelector`](crate::Error::ScopeSelector) if `selector` is not a valid scope
+    /// selector instead of panicking.
+    pub fn try_scope_override(self, selector: &str, style: Style) -> Result<Self, crate::Error> {
+        self.try_scope_style(selector, style)
+    }
+
+    // Adds a style for tokens matching the scope selector.
+    fn try_scope_style(mut self, selector: &str, style: Style) -> Result<Self, crate::Error> {
+        let style = self.adapt_style(style);
+        self.scope_styles.push(ScopeStyle {
+            selectors: ScopeSelectors::from_str(selector).map_err(crate::Error::ScopeSelector)?,
+            filter: None,
+            style,
+        });
+        Ok(self)
+    }
+
+    /// Use a secondary [`Theme`] for tokens matching any of the given scope selectors, such as
+    /// `keyword` or `markup.error`. The overlay theme's rules take precedence over the primary
+    /// theme for those tokens only. Everything else is styled using the primary theme.
+    ///
+    /// Like [`scope_background`](Self::scope_background), this has no effect on
+    /// [`highlight_line`](Self::highlight_line). Use
+    /// [`try_overlay_theme`](Self::try_overlay_theme) for selectors that come from user
+    /// configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the `selectors` are not valid scope selectors.
+    pub fn overlay_theme<S>(self, theme: Theme, selectors: Vec<S>) -> Self
+    where
+        S: AsRef<str>,
+    {
+        self.try_overlay_theme(theme, selectors)
+            .expect("invalid scope selector")
+    }
+
+    /// Use a secondary [`Theme`] for tokens matching any of the given scope selectors like
+    /// [`overlay_theme`](Self::overlay_theme), but returns
+    /// [`Error::ScopeSelector`](crate::Error::ScopeSelector) if any of the `selectors` are not
+    /// valid scope selectors instead of panicking.
+    pub fn try_overlay_theme<S>(
+        mut self,
+        theme: Theme,
+        selectors: Vec<S>,
+    ) -> Result<Self, crate::Error>
+    where
+        S: AsRef<str>,
+    {
+        let mut filter = ScopeSelectors::default();
+        for selector in &selectors {
+            let parsed =
+                ScopeSelectors::from_str(selector.as_ref()).map_err(crate::Error::ScopeSelector)?;
+            filter.selectors.extend(parsed.selectors);
+        }
+        for item in theme.scopes {
+            let style = self.adapt_style(self.converter.style_modifier_to_tui(item.style));
+            self.scope_styles.push(ScopeStyle {
+                selectors: item.scope,
+                filter: Some(filter.clone()),
+                style,
+            });
+        }
+        Ok(self)
+    }
+
+    /// Use a different [`Theme`] when highlighting the syntax with the given name, such as
+    /// `"Markdown"`. This allows a single [`Highlighter`] to serve multiple languages with themes
+    /// tailored to each one. The override replaces the primary theme entirely for that syntax,
+    /// including the background and gutter colors.
+    pub fn theme_for<S>(mut self, syntax_name: S, mut theme: Theme) -> Self
+    where
+        S: Into<String>,
+    {
+        let syntax_name = syntax_name.into();
+        if let Some(foreground) = self.default_foreground {
+            let color = self.converter.tui_color_to_syntect(foreground);
+            fill_default(&mut theme.settings.foreground, color, None);
+        }
+        if let Some(background) = self.default_background {
+            let color = self.converter.tui_color_to_syntect(background);
+            fill_default(&mut theme.settings.background, color, None);
+        }
+        self.syntax_themes.retain(|(name, _)| *name != syntax_name);
+        self.syntax_themes.push((syntax_name, theme));
+        self
+    }
+
+    /// Expand tabs into spaces, advancing to the next multiple of `tab_width`. Tab stops are
+    /// measured from the start of the code, so alignment isn't affected by the gutter width.
+    ///
+    /// Defaults to `4`. Set to `0` to leave tabs in the output as-is.
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// Render spaces and tabs at the end of each line with `style`, such as a red background, in
+    /// place of their syntax style. Tabs are expanded before the whitespace is styled. Lines that
+    /// only contain whitespace aren't marked unless
+    /// [`show_whitespace_on_blank`](Self::show_whitespace_on_blank) is set.
+    pub fn show_trailing_whitespace(mut self, style: Style) -> Self {
+        self.trailing_whitespace_style = Some(self.adapt_style(style));
+        self
+    }
+
+    /// Also mark the whitespace on lines that only contain whitespace when
+    /// [`show_trailing_whitespace`](Self::show_trailing_whitespace) is enabled. Disabled by
+    /// default.
+    pub fn show_whitespace_on_blank(mut self, show_whitespace_on_blank: bool) -> Self {
+        self.show_whitespace_on_blank = show_whitespace_on_blank;
+        self
+    }
+
+    /// Soft-wrap lines that are wider than `width` columns, including the gutter. Continuation rows
+    /// are indented by a blank gutter so the code stays aligned with the line numbers. Styles are
+    /// preserved across the wrap boundary.
+    ///
+    /// Wrapping isn't applied by [`LineHighlighter::push`] or
+    /// [`highlight_with_minimap`](Self::highlight_with_minimap), which always produce one row per
+    /// line.
+    pub fn wrap(mut self, mode: WrapMode, width: u16) -> Self {
+        self.wrap = mode;
+        self.wrap_width = width;
+        self
+    }
+
+    /// Count characters with an ambiguous East Asian width, such as `○` or `…`, as two columns
+    /// instead of one. Terminals configured for CJK locales often render these characters wide,
+    /// so this should be enabled to keep tab stops, padding, and truncation aligned on those
+    /// terminals. Disabled by default.
+    pub fn east_asian_width(mut self, east_asian_width: bool) -> Self {
+        self.east_asian_width = east_asian_width;
+        self
+    }
+
+    /// Highlight every occurrence of `query` by patching `style` over the syntax colors. Matches
+    /// can span multiple tokens, in which case each token keeps its own colors underneath `style`.
+    /// Use [`find_matches`](Self::find_matches) to get the location of each match.
+    pub fn search_highlight(mut self, query: &str, style: Style) -> Self {
+        self.search_query = (!query.is_empty()).then(|| SearchQuery::Text(query.to_string()));
+        self.search_style = self.adapt_style(style);
+        self
+    }
+
+    /// Highlight every match of `regex` by patching `style` over the syntax colors, like
+    /// [`search_highlight`](Self::search_highlight). This replaces any previous search query.
+    ///
+    /// The regex is run on each line separately, without the trailing newline, so anchors such as
+    /// `^` and `$` match the start and end of each line. Matches never overlap, following the
+    /// leftmost-first semantics of [`regex::Regex::find_iter`], and empty matches are skipped.
+    /// Case sensitivity is controlled by the regex itself, so
+    /// [`search_case_insensitive`](Self::search_case_insensitive) doesn't apply.
+    #[cfg(feature = "regex")]
+    pub fn search_highlight_regex(mut self, regex: regex::Regex, style: Style) -> Self {
+        self.search_query = Some(SearchQuery::Regex(regex));
+        self.search_style = self.adapt_style(style);
+        self
+    }
+
+    /// Highlight the bracket at `cursor` and the bracket that pairs with it by patching `style`
+    /// over both of them. The cursor is given as a 0-based line and character column. `()`,
+    /// `[]`, and `{}` are supported, and brackets inside strings or comments are ignored.
+    ///
+    /// Finding the pair can require looking ahead of the current line, so this only applies to
+    /// methods that receive the whole source up front, such as
+    /// [`highlight_lines`](Self::highlight_lines),
+    /// [`highlight_lines_borrowed`](Self::highlight_lines_borrowed), and
+    /// [`highlight_viewport`](Self::highlight_viewport). The source is parsed an extra time up to
+    /// the matching bracket.
+    pub fn match_brackets(mut self, cursor: (usize, usize), style: Style) -> Self {
+        self.bracket_match = Some((cursor, self.adapt_style(style)));
+        self
+    }
+
+    /// Highlight the text selected between `start` and `end`, which are given as 0-based line and
+    /// character columns. The selection includes `start` but not `end`, and the positions can be
+    /// given in either order. Lines between the first and last line are selected in full.
+    pub fn selection(mut self, start: (usize, usize), end: (usize, usize)) -> Self {
+        self.selection = Some((start.min(end), start.max(end)));
+        self
+    }
+
+    /// Patch `style` over the display columns `columns` of the 0-based `line`, such as a compiler
+    /// error spanning part of a line. Columns are counted in terminal cells from the start of the
+    /// code, so wide characters take up two columns and tabs expand to the next tab stop.
+    /// Characters that are only partly covered are included. Spans are patched over the
+    /// [`selection`](Self::selection), and later spans are patched over earlier ones.
+    ///
+    /// Like the selection, this applies to every method other than
+    /// [`highlight_line`](Self::highlight_line), which doesn't track line numbers.
+    pub fn highlight_span(mut self, line: usize, columns: Range<usize>, style: Style) -> Self {
+        let style = self.adapt_style(style);
+        self.highlight_spans.push((line, columns, style));
+        self
+    }
+
+    /// Set the [Style] used for the [`selection`](Self::selection). By default, the theme's
+    /// selection color is used as the background.
+    pub fn selection_style<S>(mut self, style: S) -> Self
+    where
+        S: Into<Style>,
+    {
+        self.selection_style = Some(self.adapt_style(style.into()));
+        self
+    }
+
+    /// Ignore case when matching the [`search_highlight`](Self::search_highlight) query.
+    /// Disabled by default.
+    pub fn search_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.search_case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Patch `style` over `http://` and `https://` URLs, such as links in comments and strings.
+    /// Since spans can't carry hyperlinks, use [`find_links`](Self::find_links) to get the
+    /// location of each URL if the backend supports emitting OSC 8 hyperlinks.
+    pub fn linkify(mut self, style: Style) -> Self {
+        self.link_style = Some(self.adapt_style(style));
+        self
+    }
+
+    /// Returns the location of every URL in `source` that [`linkify`](Self::linkify) would style.
+    /// URLs end at whitespace, quotes, or brackets, and trailing punctuation isn't included.
+    pub fn find_links<'a, T>(&self, source: T) -> Vec<Link>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        source
+            .into_iter()
+            .enumerate()
+            .flat_map(|(line, text)| {
+                find_urls(text).into_iter().map(move |range| Link {
+                    line,
+                    url: text[range.clone()].to_string(),
+                    range,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the location of every match of the [`search_highlight`](Self::search_highlight)
+    /// query in `source`, in the same order used by [`current_match`](Self::current_match). This
+    /// can be used to implement jumping between matches. Returns an empty list if no query is set.
+    pub fn find_matches<'a, T>(&self, source: T) -> Vec<SearchMatch>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let Some(query) = &self.search_query else {
+            return Vec::new();
+        };
+        source
+            .into_iter()
+            .enumerate()
+            .flat_map(|(line, text)| {
+                let text = strip_line_ending(text);
+                query
+                    .find(text, self.search_case_insensitive)
+                    .into_iter()
+                    .map(move |range| SearchMatch { line, range })
+            })
+            .collect()
+    }
+
+    /// Use a distinct style for the match at `index` (zero-based) from
+    /// [`search_highlight`](Self::search_highlight), such as the currently focused result in a
+    /// search UI. Matches are ordered from top to bottom, then left to right.
+    ///
+    /// Since [`highlight_line`](Self::highlight_line) highlights each line independently, matches
+    /// are counted from the start of the line there.
+    pub fn current_match(mut self, index: usize, style: Style) -> Self {
+        self.current_match = Some((index, self.adapt_style(style)));
+        self
+    }
+
+    /// Pad lines with blank space so the code section of each line is at least `width` columns
+    /// wide. The padding uses the line's background, which keeps the edge of the code block
+    /// straight when it's narrower than the area it's rendered in.
+    pub fn min_content_width(mut self, width: u16) -> Self {
+        self.min_content_width = width;
+        self
+    }
+
+    /// Set the width of the minimap produced by
+    /// [`highlight_with_minimap`](Self::highlight_with_minimap). The default is `8`.
+    pub fn minimap(mut self, width: u16) -> Self {
+        self.minimap_width = width;
+        self
+    }
+
+    /// Limit the number of code spans emitted for each line. Adjacent tokens that share a style
+    /// are merged first, then anything past the limit is merged into the last allowed span. This
+    /// bounds the size of the output for pathological grammars or inputs that produce huge numbers
+    /// of tokens.
+    pub fn max_spans_per_line(mut self, max_spans: usize) -> Self {
+        self.max_spans_per_line = Some(max_spans);
+        self
+    }
+
+    /// Set custom associations between file paths and syntax names, such as mapping
+    /// `Jenkinsfile` to `Groovy`. Patterns are matched against both the file name and the full
+    /// path. These take priority over the default detection used by
+    /// [`highlight_file`](Self::highlight_file).
+    pub fn language_map(mut self, language_map: Vec<(Pattern, String)>) -> Self {
+        self.language_map = language_map;
+        self
+    }
+
+    /// Set the syntax used when detection fails in [`highlight_file`](Self::highlight_file) and
+    /// [`highlight_str_auto`](Self::highlight_str_auto). The syntax is looked up by name, so it
+    /// must come from the same [`SyntaxSet`] that's passed to the highlight call.
+    ///
+    /// If this is `None`, the plain text syntax is used instead. If neither syntax is in the
+    /// [`SyntaxSet`], the call returns [`Error::UnknownSyntax`](crate::Error::UnknownSyntax).
+    pub fn fallback_syntax(mut self, syntax: Option<SyntaxReference>) -> Self {
+        self.fallback_syntax = syntax;
+        self
+    }
+
+    /// Pass each line to the parser with its original line ending. By default, `\r\n` line endings
+    /// are replaced with `\n` before parsing, so grammars see the same input regardless of the
+    /// line endings used by the source. Either way, line endings are never included in the
+    /// highlighted spans.
+    pub fn preserve_line_endings(mut self, preserve_line_endings: bool) -> Self {
+        self.preserve_line_endings = preserve_line_endings;
+        self
+    }
+
+    /// Replace invalid UTF-8 with `U+FFFD` when reading input, instead of returning
+    /// [`Error::Read`](crate::Error::Read). This applies to methods that read from a source, such
+    /// as [`highlight_reader`](Self::highlight_reader), [`highlight_file`](Self::highlight_file),
+    /// [`highlight_iter`](Self::highlight_iter), and `highlight_async_reader` from the `tokio`
+    /// feature. Disabled by default.
+    ///
+    /// Each line is read as raw bytes and copied into a new string, which is a bit slower than
+    /// reading UTF-8 directly. For input in other encodings, see `highlight_bytes` from the
+    /// `encoding_rs` feature.
+    pub fn lossy_utf8(mut self, lossy_utf8: bool) -> Self {
+        self.lossy_utf8 = lossy_utf8;
+        self
+    }
+
+    /// Emit an empty final line when the source ends with a newline. By default, a trailing
+    /// newline only terminates the last line, so `"a\n"` and `"a"` both produce a single line.
+    ///
+    /// This applies to methods that highlight a whole source at once, such as
+    /// [`highlight_lines`](Self::highlight_lines) and [`highlight_reader`](Self::highlight_reader).
+    pub fn keep_final_newline(mut self, keep_final_newline: bool) -> Self {
+        self.keep_final_newline = keep_final_newline;
+        self
+    }
+
+    /// Set the input size, in bytes, at which [`should_highlight_async`] recommends moving
+    /// highlighting off of the UI thread. The default is 256 KiB.
+    ///
+    /// [`should_highlight_async`]: Self::should_highlight_async
+    pub fn async_threshold(mut self, bytes: usize) -> Self {
+        self.async_threshold = bytes;
+        self
+    }
+
+    /// Render tokens whose foreground color is translucent or nearly indistinguishable from the
+    /// background using [`Modifier::DIM`](ratatui_core::style::Modifier::DIM) instead of the
+    /// faint color. Disabled by default.
+    pub fn dim_low_contrast(mut self, dim_low_contrast: bool) -> Self {
+        self.converter = self.converter.dim_low_contrast(dim_low_contrast);
+        self
+    }
+
+    /// Quantize the theme's colors to the nearest color available at the given [`ColorDepth`].
+    /// See [`Converter::downsample`] for details. Styles passed to the highlighter directly, such
+    /// as [`line_number_style`](Self::line_number_style), are used as-is.
+    pub fn downsample(mut self, depth: ColorDepth) -> Self {
+        self.converter = self.converter.downsample(depth);
+        self
+    }
+
+    /// Render without any colors, keeping modifiers such as bold, italic, and underline from the
+    /// theme and any configured styles. Unlike the `NoTty` profile from `termprofile`, which
+    /// removes all styling, this keeps the text structure visible on monochrome terminals.
+    /// Disabled by default.
+    pub fn monochrome(mut self, monochrome: bool) -> Self {
+        self.monochrome = monochrome;
+        self
+    }
+
+    /// Set a function that renders the line number separator for each line. This is an
+    /// alternative to [`line_number_separator`] and [`line_number_separator_style`] that allows the
+    /// separator to vary per line, such as using a heavier glyph on the current line. The function
+    /// receives the 1-based line number and the [`LineFlags`] for the line.
+    ///
+    /// [`line_number_separator`]: Self::line_number_separator
+    /// [`line_number_separator_style`]: Self::line_number_separator_style
+    pub fn line_number_separator_fn<F>(mut self, separator: F) -> Self
+    where
+        F: Fn(usize, LineFlags) -> Span<'static> + Send + Sync + 'static,
+    {
+        self.separator_template = Some(SeparatorTemplate(Arc::new(separator)));
+        self
+    }
+
+    /// Set a template function to configure the gutter section. This is an alternative to using
+    /// [`line_number_style`], [`line_number_separator_style`], and [`line_number_padding`] if you
+    /// need more flexibility.
+    ///
+    /// [`line_number_style`]: Self::line_number_style
+    /// [`line_number_separator_style`]: Self::line_number_separator_style
+    /// [`line_number_padding`]: Self::line_number_padding
+    pub fn gutter_template<F>(self, template: F) -> Self
+    where
+        F: Fn(usize, Style) -> Vec<Span<'static>> + Send + Sync + 'static,
+    {
+        self.gutter_template_with_context(move |context| {
+            template(context.line_number, context.base_style)
+        })
+    }
+
+    /// Like [`gutter_template`](Self::gutter_template), but the function receives a
+    /// [`GutterContext`] describing the line, so the gutter can vary per line, such as drawing a
+    /// marker on the current line.
+    pub fn gutter_template_with_context<F>(mut self, template: F) -> Self
+    where
+        F: Fn(GutterContext) -> Vec<Span<'static>> + Send + Sync + 'static,
+    {
+        self.gutter_template = Some(GutterTemplate(Arc::new(template)));
+        self
+    }
+
+    /// Returns the [`Theme`] used for highlighting. This can be used to style other parts of the UI
+    /// consistently with the code, such as drawing a cursor with `theme().settings.caret`.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Returns the name of the theme passed to [`new_named`](Self::new_named), or the name stored
+    /// in the theme itself if it wasn't given one.
+    pub fn theme_name(&self) -> Option<&str> {
+        self.theme_name.as_deref().or(self.theme.name.as_deref())
+    }
+
+    /// Returns the configured background color, accounting for both the theme and any overrides.
+    /// This is useful if you want to render the code block into a larger section and you need the
+    /// background colors to match.
+    pub fn get_background_color(&self) -> Option<Color> {
+        if self.monochrome {
+            return None;
+        }
+        match self.override_background {
+            OverrideBackground::Color(bg) => Some(bg),
+            OverrideBackground::None => None,
+            OverrideBackground::Theme => self
+                .theme
+                .settings
+                .background
+                .and_then(|bg| self.converter.syntect_color_to_tui(bg)),
+        }
+    }
+
+    /// Returns the configured gutter background, accounting for both the code background and any
+    /// overrides. If the code background isn't an RGB color, the same color is used for the
+    /// gutter.
+    pub fn get_gutter_background(&self) -> Option<Color> {
+        if self.monochrome {
+            return None;
+        }
+        if let Some(bg) = self.gutter_background {
+            return Some(bg);
+        }
+        match self.get_background_color()? {
+            Color::Rgb(r, g, b) => {
+                let shade = |c: u8| (u16::from(c) * GUTTER_SHADE / 100) as u8;
+                Some(Color::Rgb(shade(r), shade(g), shade(b)))
+            }
+            bg => Some(bg),
+        }
+    }
+
+    /// Returns the configured line number style, accounting for both the theme and any overrides.
+    pub fn get_line_number_style(&self) -> Style {
+        if let Some(style) = self.line_number_style {
+            return style;
+        }
+        let mut style = Style::new();
+        if let Some(fg) = self
+            .theme
+            .settings
+            .gutter_foreground
+            .and_then(|fg| self.converter.syntect_color_to_tui(fg))
+        {
+            style = style.fg(fg);
+        } else if let Some(fg) = self.default_foreground {
+            style = style.fg(fg);
+        } else {
+            style = style.dark_gray();
+        }
+        if let Some(bg) = self.get_gutter_background() {
+            style = style.bg(bg);
+        }
+        self.adapt_style(style)
+    }
+
+    /// Returns the configured style for the [`current_line`](Self::current_line), accounting for
+    /// both the theme and any overrides.
+    pub fn get_current_line_style(&self) -> Style {
+        if let Some(style) = self.current_line_style {
+            return style;
+        }
+        let bg = self
+            .theme
+            .settings
+            .line_highlight
+            .and_then(|bg| self.converter.syntect_color_to_tui(bg))
+            .unwrap_or(Color::DarkGray);
+        self.adapt_style(Style::new().bg(bg))
+    }
+
+    /// Returns the configured style for the [`selection`](Self::selection), accounting for both
+    /// the theme and any overrides.
+    pub fn get_selection_style(&self) -> Style {
+        if let Some(style) = self.selection_style {
+            return style;
+        }
+        let bg = self
+            .theme
+            .settings
+            .selection
+            .and_then(|bg| self.converter.syntect_color_to_tui(bg))
+            .unwrap_or(Color::DarkGray);
+        self.adapt_style(Style::new().bg(bg))
+    }
+
+    /// Returns the configured style for the line number separator on the
+    /// [`current_line`](Self::current_line), accounting for both the theme and any overrides.
+    pub fn get_current_line_separator_style(&self) -> Style {
+        if let Some(style) = self.current_line_separator_style {
+            return style;
+        }
+        let mut style = self.get_line_number_style();
+        if let Some(fg) = self
+            .theme
+            .settings
+            .foreground
+            .and_then(|fg| self.converter.syntect_color_to_tui(fg))
+        {
+            style = style.fg(fg);
+        } else {
+            style = style.white();
+        }
+        self.adapt_style(style)
+    }
+
+    /// Returns `true` if input of the given size is large enough that highlighting should be
+    /// offloaded to a worker thread, based on the configured
+    /// [`async_threshold`](Self::async_threshold).
+    pub fn should_highlight_async(&self, byte_len: usize) -> bool {
+        byte_len >= self.async_threshold
+    }
+
+    /// Returns a cheap estimate of the number of lines that will be produced when highlighting
+    /// `source`.
+    pub fn estimate_lines(source: &str) -> usize {
+        let newlines = source.bytes().filter(|b| *b == b'\n').count();
+        if source.is_empty() || source.ends_with('\n') {
+            newlines
+        } else {
+            newlines + 1
+        }
+    }
+
+    /// Finds foldable regions in `source` based on indentation. Each returned range contains the
+    /// zero-based indices of the lines that are indented further than the line preceding them,
+    /// so the header line stays visible when the region is folded. Blank lines inside a region
+    /// are included, but trailing blank lines are not. Tabs count as four columns.
+    pub fn indentation_folds(source: &str) -> Vec<Range<usize>> {
+        let indents: Vec<_> = source.lines().map(indentation).collect();
+        let mut folds = Vec::new();
+        for (header, indent) in indents.iter().enumerate() {
+            let Some(indent) = indent else {
+                continue;
+            };
+            let mut end = header;
+            for (i, body_indent) in indents.iter().enumerate().skip(header + 1) {
+                match body_indent {
+                    Some(body_indent) if body_indent <= indent => break,
+                    Some(_) => end = i,
+                    None => {}
+                }
+            }
+            if end > header {
+                folds.push(header + 1..end + 1);
+            }
+        }
+        folds
+    }
+
+    // Returns the theme used for the syntax, accounting for any override set with theme_for.
+    pub(crate) fn syntax_theme(&self, syntax: &SyntaxReference) -> &Theme {
+        self.syntax_themes
+            .iter()
+            .find(|(name, _)| *name == syntax.name)
+            .map_or(&self.theme, |(_, theme)| theme)
+    }
+
+    // Returns a highlighter that uses the theme registered for the syntax, if any.
+    pub(crate) fn for_syntax(&self, syntax: &SyntaxReference) -> Cow<'_, Self> {
+        match self
+            .syntax_themes
+            .iter()
+            .find(|(name, _)| *name == syntax.name)
+        {
+            Some((_, theme)) => Cow::Owned(Self {
+                theme: theme.clone(),
+                syntax_themes: Vec::new(),
+                ..self.clone()
+            }),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// Creates a [`LineHighlighter`] for highlighting content one line at a time.
+    pub fn line_highlighter<'a>(
+        &'a self,
+        syntax: &SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+    ) -> LineHighlighter<'a> {
+        LineHighlighter::new(self, syntax, syntaxes)
+    }
+
+    /// Creates a [`Transcript`] for building up highlighted content one fragment at a time.
+    pub fn transcript<'a>(
+        &'a self,
+        syntax: &SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+    ) -> Transcript<'a> {
+        Transcript::new(self, syntax, syntaxes)
+    }
+
+    /// Highlights text from any [`io::Read`] source.
+    pub fn highlight_reader<R>(
+        &self,
+        reader: R,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        R: io::Read,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut highlighter = self.line_highlighter(syntax, syntaxes);
+        let mut line = String::new();
+        let mut formatted = Vec::new();
+        while self
+            .read_line(&mut reader, &mut line)
+            .map_err(crate::Error::Read)?
+            > 0
+        {
+            formatted.extend(highlighter.push_rows(&line)?);
+            line.clear();
+        }
+        formatted.extend(highlighter.finish()?);
+        Ok(Text::from_iter(formatted))
+    }
+
+    /// Highlights text from any [`io::Read`] source like
+    /// [`highlight_reader`](Self::highlight_reader), returning a [`HighlightedText`] that
+    /// carries the background color of the code block.
+    pub fn highlight_reader_widget<R>(
+        &self,
+        reader: R,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<HighlightedText<'static>, crate::Error>
+    where
+        R: io::Read,
+    {
+        let text = self.highlight_reader(reader, syntax, syntaxes)?;
+        Ok(self.for_syntax(syntax).highlighted_text(text))
+    }
+
+    /// Returns an iterator that reads and highlights one line at a time from `reader`, so large
+    /// inputs can be rendered incrementally without collecting the whole source first.
+    pub fn highlight_iter<'a, R>(
+        &'a self,
+        reader: R,
+        syntax: &SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+    ) -> HighlightIter<'a, R>
+    where
+        R: BufRead,
+    {
+        HighlightIter::new(reader, self.line_highlighter(syntax, syntaxes))
+    }
+
+    /// Returns a [`Stream`](futures_util::Stream) that reads and highlights one line at a time
+    /// from an async `reader`, like [`highlight_iter`](Self::highlight_iter). The parse state is
+    /// kept inside the stream between reads.
+    ///
+    /// The stream isn't [`Send`] because syntect's parse state isn't, so it must be polled from
+    /// the task that created it. Use a `LocalSet` or `spawn_local` to run
+    /// it alongside other tasks, or
+    /// [`highlight_reader_streaming`](Self::highlight_reader_streaming) on a blocking thread if
+    /// the lines need to be highlighted elsewhere.
+    #[cfg(feature = "tokio")]
+    pub fn highlight_async_reader<'a, R>(
+        &'a self,
+        reader: R,
+        syntax: &SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+    ) -> impl futures_util::Stream<Item = Result<Line<'static>, crate::Error>> + 'a
+    where
+        R: tokio::io::AsyncBufRead + Unpin + 'a,
+    {
+        let iter = HighlightIter::new(reader, self.line_highlighter(syntax, syntaxes));
+        futures_util::stream::unfold(iter, |mut iter| async move {
+            let next = iter.next_async().await?;
+            Some((next, iter))
+        })
+    }
+
+    /// Highlights text from any [`io::Read`] source, sending each line through `tx` as soon as
+    /// it's highlighted. This is useful for highlighting large files on a worker thread while the
+    /// UI renders the lines received so far.
+    ///
+    /// Highlighting stops after the first error is sent, or once the receiver is dropped.
+    pub fn highlight_reader_streaming<R>(
+        &self,
+        reader: R,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+        tx: Sender<Result<Line<'static>, crate::Error>>,
+    ) where
+        R: io::Read,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut highlighter = self.line_highlighter(syntax, syntaxes);
+        let mut line = String::new();
+        loop {
+            match self.read_line(&mut reader, &mut line) {
+                Ok(0) => {
+                    if let Some(finished) = highlighter.finish().transpose() {
+                        let _ = tx.send(finished);
+                    }
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let _ = tx.send(Err(crate::Error::Read(e)));
+                    return;
+                }
+            }
+            let highlighted = highlighter.push_rows(&line);
+            line.clear();
+            match highlighted {
+                Ok(rows) => {
+                    for row in rows {
+                        if tx.send(Ok(row)).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Highlights raw bytes in the given [`Encoding`](encoding_rs::Encoding), such as
+    /// Windows-1252 or Shift JIS. The input is decoded to UTF-8 before highlighting, and any
+    /// malformed sequences are replaced with `U+FFFD`. A byte order mark, if present, takes
+    /// precedence over `encoding`.
+    #[cfg(feature = "encoding_rs")]
+    pub fn highlight_bytes(
+        &self,
+        bytes: &[u8],
+        encoding: &'static encoding_rs::Encoding,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error> {
+        let (decoded, _, _) = encoding.decode(bytes);
+        self.highlight_lines(decoded.split_inclusive('\n'), syntax, syntaxes)
+    }
+
+    /// Highlights the file at the given path. The syntax is detected using the
+    /// [`language_map`](Self::language_map), then the file name and extension, then the first line
+    /// of the file. The [`fallback_syntax`](Self::fallback_syntax) is used if no syntax matches.
+    ///
+    /// Returns [`Error::Read`](crate::Error::Read) if the file can't be opened or contains invalid
+    /// UTF-8 without [`lossy_utf8`](Self::lossy_utf8) enabled, or
+    /// [`Error::UnknownSyntax`](crate::Error::UnknownSyntax) if detection fails and the
+    /// fallback syntax isn't available.
+    pub fn highlight_file<P>(
+        &self,
+        path: P,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut reader = BufReader::new(File::open(path).map_err(crate::Error::Read)?);
+        let mut line = String::new();
+        self.read_line(&mut reader, &mut line)
+            .map_err(crate::Error::Read)?;
+        let syntax = self.find_syntax_for_path(path, &line, syntaxes)?;
+
+        let mut highlighter = self.line_highlighter(syntax, syntaxes);
+        let mut formatted = Vec::new();
+        while !line.is_empty() {
+            formatted.extend(highlighter.push_rows(&line)?);
+            line.clear();
+            self.read_line(&mut reader, &mut line)
+                .map_err(crate::Error::Read)?;
+        }
+        formatted.extend(highlighter.finish()?);
+        Ok(Text::from_iter(formatted))
+    }
+
+    /// Highlights `source`, detecting the syntax from `path` the same way as
+    /// [`highlight_file`](Self::highlight_file). The path is only used for detection, so it
+    /// doesn't need to exist. Files without a recognized name or extension fall back to detection
+    /// from the first line, then to the [`fallback_syntax`](Self::fallback_syntax). An empty
+    /// `source` is detected from the path alone and produces empty [`Text`].
+    pub fn highlight_str_auto<P>(
+        &self,
+        source: &str,
+        path: P,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let first_line = source.split_inclusive('\n').next().unwrap_or_default();
+        let syntax = self.find_syntax_for_path(path.as_ref(), first_line, syntaxes)?;
+        self.highlight_lines(source.split_inclusive('\n'), syntax, syntaxes)
+    }
+
+    /// Highlights text from a buffered reader, finding the syntax with `hint` if one is given.
+    /// Otherwise, or if the hint doesn't match any syntax, the syntax is detected from the first
+    /// line, then falls back to the [`fallback_syntax`](Self::fallback_syntax). The first line is
+    /// peeked from the reader's buffer, so it's still highlighted along with the rest of the
+    /// content.
+    ///
+    /// Returns [`Error::UnknownSyntax`](crate::Error::UnknownSyntax) if detection fails and the
+    /// fallback syntax isn't available.
+    pub fn highlight_reader_auto<R>(
+        &self,
+        mut reader: R,
+        hint: Option<SyntaxHint<'_>>,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        R: BufRead,
+    {
+        let buffered = reader.fill_buf().map_err(crate::Error::Read)?;
+        let first_line = buffered
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(buffered, |end| &buffered[..=end]);
+        // The buffer may end partway through a character, so only use the valid prefix.
+        let first_line = match std::str::from_utf8(first_line) {
+            Ok(line) => line,
+            Err(e) => std::str::from_utf8(&first_line[..e.valid_up_to()]).unwrap_or_default(),
+        };
+        let syntax = hint
+            .and_then(|hint| self.resolve_syntax(hint, syntaxes))
+            .or_else(|| self.resolve_syntax(SyntaxHint::FirstLine(first_line), syntaxes))
+            .or_else(|| self.find_fallback_syntax(syntaxes))
+            .ok_or(crate::Error::UnknownSyntax)?;
+        self.highlight_reader(reader, syntax, syntaxes)
+    }
+
+    /// Highlights everything piped into standard input like
+    /// [`highlight_reader_auto`](Self::highlight_reader_auto). This is convenient for CLI tools
+    /// that accept code through a pipe, such as `cat main.rs | tool --language rust`.
+    pub fn highlight_stdin(
+        &self,
+        hint: Option<SyntaxHint<'_>>,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error> {
+        self.highlight_reader_auto(io::stdin().lock(), hint, syntaxes)
+    }
+
+    /// Highlights each file in `paths` like [`highlight_file`](Self::highlight_file), using a
+    /// [rayon](https://docs.rs/rayon) thread pool to process multiple files at once. The results
+    /// are returned in the same order as `paths`.
+    ///
+    /// Only separate files are highlighted in parallel. Each file is still parsed sequentially,
+    /// since the parse state of every line depends on the lines before it.
+    #[cfg(feature = "rayon")]
+    pub fn highlight_files_parallel<P>(
+        &self,
+        paths: &[P],
+        syntaxes: &SyntaxSet,
+    ) -> Vec<Result<Text<'static>, crate::Error>>
+    where
+        P: AsRef<Path> + Sync,
+    {
+        use rayon::prelude::*;
+
+        paths
+            .par_iter()
+            .map(|path| self.highlight_file(path, syntaxes))
+            .collect()
+    }
+
+    /// Finds a syntax in `syntaxes` using the given [`SyntaxHint`], returning `None` if nothing
+    /// matches.
+    pub fn resolve_syntax<'s>(
+        &self,
+        hint: SyntaxHint<'_>,
+        syntaxes: &'s SyntaxSet,
+    ) -> Option<&'s SyntaxReference> {
+        match hint {
+            SyntaxHint::Name(name) => syntaxes.find_syntax_by_name(name),
+            SyntaxHint::Extension(extension) => syntaxes.find_syntax_by_extension(extension),
+            SyntaxHint::FirstLine(line) => syntaxes.find_syntax_by_first_line(line),
+            SyntaxHint::Token(token) => syntaxes.find_syntax_by_token(token),
+        }
+    }
+
+    fn find_syntax_for_path<'s>(
+        &self,
+        path: &Path,
+        first_line: &str,
+        syntaxes: &'s SyntaxSet,
+    ) -> Result<&'s SyntaxReference, crate::Error> {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        let mapped = self.language_map.iter().find_map(|(pattern, name)| {
+            let is_match = pattern.matches_path(path)
+                || file_name.is_some_and(|file_name| pattern.matches(file_name));
+            if is_match {
+                self.resolve_syntax(SyntaxHint::Name(name), syntaxes)
+            } else {
+                None
+            }
+        });
+        mapped
+            .or_else(|| {
+                file_name
+                    .and_then(|name| self.resolve_syntax(SyntaxHint::Extension(name), syntaxes))
+            })
+            .or_else(|| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .and_then(|extension| {
+                        self.resolve_syntax(SyntaxHint::Extension(extension), syntaxes)
+                    })
+            })
+            .or_else(|| self.resolve_syntax(SyntaxHint::FirstLine(first_line), syntaxes))
+            .or_else(|| self.find_fallback_syntax(syntaxes))
+            .ok_or(crate::Error::UnknownSyntax)
+    }
+
+    fn find_fallback_syntax<'s>(&self, syntaxes: &'s SyntaxSet) -> Option<&'s SyntaxReference> {
+        let name = self
+            .fallback_syntax
+            .as_ref()
+            .map_or("Plain Text", |syntax| syntax.name.as_str());
+        self.resolve_syntax(SyntaxHint::Name(name), syntaxes)
+    }
+
+    /// Highlights text from an iterator.
+    pub fn highlight_lines<'a, T>(
+        &self,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        self.highlight_lines_sized(source, 0, syntax, syntaxes)
+    }
+
+    /// Highlights text from an iterator like [`highlight_lines`](Self::highlight_lines), returning
+    /// a [`HighlightedText`] that carries the background color of the code block.
+    pub fn highlight_lines_widget<'a, T>(
+        &self,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<HighlightedText<'static>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let text = self.highlight_lines(source, syntax, syntaxes)?;
+        Ok(self.for_syntax(syntax).highlighted_text(text))
+    }
+
+    /// Highlights text from an iterator like [`highlight_lines`](Self::highlight_lines), reserving
+    /// space for `count` lines up front. This avoids reallocating the output for large inputs when
+    /// the number of lines is known but the iterator doesn't report its length.
+    pub fn highlight_lines_sized<'a, T>(
+        &self,
+        source: T,
+        count: usize,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        if self.bracket_match.is_some() {
+            // Matching brackets can be anywhere in the source, so it needs to be collected first.
+            let lines: Vec<_> = source.into_iter().collect();
+            let highlighter = self
+                .line_highlighter(syntax, syntaxes)
+                .with_total_lines(lines.len())
+                .with_brackets(&lines, syntax)?;
+            return collect_rows(highlighter, lines.into_iter(), count);
+        }
+        let source = source.into_iter();
+        let total = exact_len(&source).unwrap_or(count);
+        let highlighter = self
+            .line_highlighter(syntax, syntaxes)
+            .with_total_lines(total);
+        collect_rows(highlighter, source, count)
+    }
+
+    /// Highlights text from an iterator like [`highlight_lines`](Self::highlight_lines), returning
+    /// the [`ScopedSpan`]s of each line alongside it. This can be used to find the scope under a
+    /// position on screen, such as a mouse click.
+    ///
+    /// Scopes are only collected if [`with_scopes`](Self::with_scopes) is enabled. Otherwise, the
+    /// lists of spans are empty. Each entry corresponds to one line of the source, so lines are
+    /// never wrapped or hidden by folds.
+    pub fn highlight_lines_scoped<'a, T>(
+        &self,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Vec<(Line<'static>, Vec<ScopedSpan>)>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let mut highlighter = self.line_highlighter(syntax, syntaxes);
+        source
+            .into_iter()
+            .map(|line| {
+                let (highlighted, scopes) = highlighter.push_scoped(line)?;
+                Ok((spans::into_owned(highlighted), scopes))
+            })
+            .collect()
+    }
+
+    /// Highlights text from an iterator like [`highlight_lines`](Self::highlight_lines), cutting
+    /// off each line so it fits within `width` columns, including the gutter. Lines that don't fit
+    /// end with the [`truncate_marker`](Self::truncate_marker). Widths are measured in terminal
+    /// columns, so wide characters such as CJK text take up two columns.
+    pub fn highlight_lines_width<'a, T>(
+        &self,
+        source: T,
+        width: u16,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let this = self.for_syntax(syntax);
+        let mut text = self.highlight_lines(source, syntax, syntaxes)?;
+        let width = usize::from(width);
+        let marker = this.truncate_marker.as_deref().unwrap_or_default();
+        let marker_style = this.truncate_marker_style();
+        for line in &mut text.lines {
+            spans::truncate_line(line, width, marker, marker_style, this.east_asian_width);
+        }
+        Ok(text)
+    }
+
+    /// Highlights a slice of lines like [`highlight_lines`](Self::highlight_lines), but returns
+    /// [`Text`] that borrows from `lines` instead of copying each token into a new [`String`].
+    /// Spans that need to be modified, such as those with expanded tabs or search matches, are
+    /// still allocated.
+    pub fn highlight_lines_borrowed<'a>(
+        &self,
+        lines: &'a [&'a str],
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'a>, crate::Error> {
+        let mut highlighter = self
+            .line_highlighter(syntax, syntaxes)
+            .with_total_lines(lines.len())
+            .with_brackets(lines, syntax)?;
+        let mut formatted = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            if line.is_empty() && i + 1 == lines.len() && highlighter.ends_with_newline() {
+                // The previous line already ended with a newline, so this empty line is only the
+                // remainder after it rather than a blank line in the source.
+                break;
+            }
+            formatted.extend(highlighter.push_rows_borrowed(line)?);
+        }
+        formatted.extend(highlighter.finish()?);
+        Ok(Text::from_iter(formatted))
+    }
+
+    /// Highlights `lines` using the state stored in `cache`, only highlighting lines starting from
+    /// `dirty_from`, which should be the index of the first line that changed since the previous
+    /// call. Everything cached at or after `dirty_from` is discarded and highlighted again, since
+    /// an edit can change how the rest of the file is parsed. Pass `0` to highlight everything.
+    ///
+    /// Cached rows before `dirty_from` are also highlighted again if their decorations changed,
+    /// such as when the [`current_line`](Self::current_line) or [`selection`](Self::selection)
+    /// moves. Every row is highlighted again when
+    /// [`auto_line_number_width`](Self::auto_line_number_width) changes the width of the gutter or
+    /// the [`search_highlight`](Self::search_highlight) query changes. When
+    /// [`match_brackets`](Self::match_brackets) is enabled, the search for the matching bracket
+    /// starts from the cached state near the cursor rather than the start of the file.
+    ///
+    /// The cache stores a copy of the parse state for every line, so memory usage grows with the
+    /// size of the buffer.
+    pub fn highlight_with_cache(
+        &self,
+        lines: &[&str],
+        cache: &mut LineCache,
+        dirty_from: usize,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error> {
+        cache.invalidate(dirty_from.min(lines.len()));
+        let highlighter = self
+            .line_highlighter(syntax, syntaxes)
+            .with_total_lines(lines.len());
+        cache.sync(
+            highlighter.highlighter().line_number_padding,
+            highlighter.highlighter().search(),
+        );
+        let mut highlighter = highlighter.with_cached_brackets(lines, syntax, cache)?;
+        let initial = highlighter.state();
+        for (i, line) in lines[..cache.len()].iter().enumerate() {
+            let key = highlighter.row_key(i, line, cache.matches(i));
+            if *cache.key(i) != key {
+                highlighter.restore(cache.state_before(i).unwrap_or(&initial));
+                let rows = highlighter.push_rows(line)?;
+                cache.replace(i, rows, key);
+            }
+        }
+        highlighter.restore(cache.last_state().unwrap_or(&initial));
+        for (i, line) in lines.iter().enumerate().skip(cache.len()) {
+            let first_match = highlighter.match_index();
+            let rows = highlighter.push_rows(line)?;
+            let key = highlighter.row_key(i, line, first_match..highlighter.match_index());
+            cache.push(rows, highlighter.state(), key);
+        }
+        let mut formatted: Vec<_> = cache.rows().cloned().collect();
+        formatted.extend(highlighter.finish()?);
+        Ok(Text::from_iter(formatted))
+    }
+
+    /// Highlights only the lines of `lines` within `range`, such as the rows visible in a scrolled
+    /// view. Lines before `range.start` are still parsed so that multi-line constructs are
+    /// highlighted correctly, but their styled output is never built. Lines after `range.end` are
+    /// not processed at all. The gutter shows the absolute line numbers of the returned lines.
+    pub fn highlight_viewport(
+        &self,
+        lines: &[&str],
+        range: Range<usize>,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error> {
+        let end = range.end.min(lines.len());
+        let start = range.start.min(end);
+        let mut highlighter = self
+            .line_highlighter(syntax, syntaxes)
+            .with_total_lines(lines.len())
+            .with_brackets(lines, syntax)?;
+        for line in &lines[..start] {
+            highlighter.skip(line)?;
+        }
+        let mut formatted = Vec::with_capacity(end - start);
+        for line in &lines[start..end] {
+            formatted.extend(highlighter.push_rows(line)?);
+        }
+        if end == lines.len() {
+            formatted.extend(highlighter.finish()?);
+        }
+        Ok(Text::from_iter(formatted))
+    }
+
+    /// Highlights text from an iterator and wraps it in a [`Paragraph`] with the given [`Block`].
+    /// The paragraph's background is set to match the code block, so any space around the text,
+    /// including the block's borders and padding, uses the same color.
+    pub fn into_block_paragraph<'a, T>(
+        &self,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+        block: Block<'static>,
+    ) -> Result<Paragraph<'static>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        Ok(self
+            .highlight_lines_widget(source, syntax, syntaxes)?
+            .into_paragraph()
+            .block(block))
+    }
+
+    /// Highlights a unified diff, such as the output of `git diff`. The content of each line in a
+    /// hunk is highlighted with `syntax`, and added and removed lines are patched with the
+    /// [`diff_add_style`](Self::diff_add_style) and [`diff_remove_style`](Self::diff_remove_style).
+    /// The `+`, `-`, or space prefix is moved after the gutter, and line numbers follow the new
+    /// file for added and context lines and the old file for removed lines.
+    ///
+    /// The old and new versions of the file are parsed separately, so constructs that span
+    /// multiple lines are highlighted correctly on both sides. Hunk headers are styled with the
+    /// [`diff_hunk_style`](Self::diff_hunk_style), and other lines outside of hunks, such as
+    /// file headers, are left unstyled.
+    pub fn highlight_diff(
+        &self,
+        diff: &str,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Text<'static>, crate::Error> {
+        let this = self.for_syntax(syntax);
+        let line_number_style = this.get_line_number_style();
+        let mut old = this.line_highlighter(syntax, syntaxes);
+        let mut new = this.line_highlighter(syntax, syntaxes);
+        let mut remaining = (0, 0);
+        let mut lines = Vec::new();
+        for line in diff.lines() {
+            if let Some(hunk) = HunkHeader::parse(line) {
+                old.set_line_number(hunk.old_start.saturating_sub(1));
+                new.set_line_number(hunk.new_start.saturating_sub(1));
+                remaining = (hunk.old_len, hunk.new_len);
+                lines.push(
+                    this.apply_background(Line::styled(line.to_string(), this.diff_hunk_style)),
+                );
+                continue;
+            }
+            let (prefix, code) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+            let (highlighter, style) = match prefix {
+                "+" if remaining.1 > 0 => {
+                    remaining.1 -= 1;
+                    (&mut new, Some(this.diff_add_style))
+                }
+                "-" if remaining.0 > 0 => {
+                    remaining.0 -= 1;
+                    (&mut old, Some(this.diff_remove_style))
+                }
+                " " | "" if remaining.0 > 0 && remaining.1 > 0 => {
+                    remaining.0 -= 1;
+                    remaining.1 -= 1;
+                    old.skip(code)?;
+                    (&mut new, None)
+                }
+                _ => {
+                    lines.push(this.apply_background(Line::raw(line.to_string())));
+                    continue;
+                }
+            };
+            let gutter_len = this
+                .get_initial_spans(highlighter.line_count(), line_number_style)
+                .len();
+            let mut highlighted = highlighter.push(code)?;
+            let prefix = Span::styled(prefix.to_string(), line_number_style);
+            highlighted
+                .spans
+                .insert(gutter_len.min(highlighted.spans.len()), prefix);
+            if let Some(style) = style {
+                highlighted = highlighted.patch_style(style);
+            }
+            lines.push(highlighted);
+        }
+        Ok(Text::from_iter(lines))
+    }
+
+    /// Highlights text from an iterator and annotates it with compiler errors. The columns
+    /// covered by each error are underlined, and the error message is rendered on a separate row
+    /// beneath the offending line, pointing at the error with carets.
+    pub fn render_with_errors<'a, T>(
+        &self,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+        errors: &[CompilerError],
+    ) -> Result<HighlightedText<'static>, crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let this = self.for_syntax(syntax);
+        let mut highlighter = this.line_highlighter(syntax, syntaxes);
+        let mut lines = Vec::new();
+        for (line_number, source_line) in source.into_iter().enumerate() {
+            if this.is_folded(line_number) {
+                // Errors inside a fold are hidden along with their lines.
+                lines.extend(highlighter.push_visible(source_line)?);
+                continue;
+            }
+            let full_line = source_line;
+            let source_line = source_line.trim_end_matches(['\r', '\n']);
+            let line_errors: Vec<_> = errors
+                .iter()
+                .filter(|error| error.line == line_number + 1)
+                .collect();
+            let ranges: Vec<_> = line_errors
+                .iter()
+                .map(|error| {
+                    let range = char_range_to_bytes(source_line, &error.col_range);
+                    (range, Style::new().add_modifier(Modifier::UNDERLINED))
+                })
+                .collect();
+            // The underlines are patched before tabs are expanded, so the byte ranges line up.
+            let line = highlighter.push_patched(full_line, &ranges)?;
+            lines.extend(this.wrap_line(line, line_number));
+
+            let gutter = this.get_initial_spans(line_number, this.get_line_number_style());
+
+            let gutter_width = spans::spans_width(&gutter, this.east_asian_width);
+            let width = |text| spans::expanded_width(text, this.tab_width, this.east_asian_width);
+            for (error, (range, _)) in line_errors.iter().zip(&ranges) {
+                let offset = width(&source_line[..range.start]);
+                // Measure from the start of the line so tabs inside the range expand correctly.
+                let carets = width(&source_line[..range.end])
+                    .saturating_sub(offset)
+                    .max(1);
+                let annotation = Line::from_iter([
+                    Span::styled(" ".repeat(gutter_width), this.get_line_number_style()),
+                    Span::raw(" ".repeat(offset)),
+                    Span::styled(
+                        format!("{} {}", "^".repeat(carets), error.message),
+                        this.error_style,
+                    ),
+                ]);
+                lines.push(this.apply_background(annotation));
+            }
+        }
+        Ok(this.highlighted_text(Text::from(lines)))
+    }
+
+    /// Highlights text from an iterator, returning the highlighted code along with a condensed
+    /// minimap that can be rendered in a narrow column beside it. Each line of the minimap
+    /// summarizes the same line of code as a row of colored blocks, where each block takes the
+    /// most common token color in the section of the line it covers. The minimap is scaled so the
+    /// longest line fills its [`width`](Self::minimap).
+    pub fn highlight_with_minimap<'a, T>(
+        &self,
+        source: T,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+    ) -> Result<(HighlightedText<'static>, HighlightedText<'static>), crate::Error>
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let this = self.for_syntax(syntax);
+        let mut highlighter = this.line_highlighter(syntax, syntaxes);
+        let line_number_style = this.get_line_number_style();
+        let mut lines = Vec::new();
+        let mut gutter_lens = Vec::new();
+        for line in source {
+            let line_number = highlighter.line_count();
+            if let Some(line) = highlighter.push_visible(line)? {
+                gutter_lens.push(this.get_initial_spans(line_number, line_number_style).len());
+                lines.push(line);
+            }
+        }
+
+        let code_spans: Vec<_> = lines
+            .iter()
+            .zip(gutter_lens)
+            .map(|(line, gutter_len)| &line.spans[gutter_len.min(line.spans.len())..])
+            .collect();
+        let max_width = code_spans
+            .iter()
+            .map(|spans| spans::spans_width(spans, this.east_asian_width))
+            .max()
+            .unwrap_or(0);
+        let width = usize::from(this.minimap_width);
+        let scale = max_width.div_ceil(width.max(1)).max(1);
+        let minimap: Vec<_> = code_spans
+            .iter()
+            .map(|spans| {
+                this.apply_background(spans::minimap_row(
+                    spans,
+                    width,
+                    scale,
+                    this.east_asian_width,
+                ))
+            })
+            .collect();
+
+        Ok((
+            this.highlighted_text(Text::from(lines)),
+            this.highlighted_text(Text::from(minimap)),
+        ))
+    }
+
+    /// Highlights a snippet of code and wraps it in a [`Paragraph`] with a rounded, bordered
+    /// [`Block`] using the given title. Trailing blank lines are removed, and the background is
+    /// set to match the code block like [`into_block_paragraph`](Self::into_block_paragraph).
+    pub fn snippet(
+        &self,
+        source: &str,
+        syntax: &SyntaxReference,
+        syntaxes: &SyntaxSet,
+        title: &str,
+    ) -> Result<Paragraph<'static>, crate::Error> {
+        let mut lines: Vec<_> = source.split_inclusive('\n').collect();
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(title.to_string());
+        self.into_block_paragraph(lines, syntax, syntaxes, block)
+    }
+
+    /// Highlights a single line.
+    pub fn highlight_line(
+        &self,
+        line: &str,
+        highlighter: &mut HighlightLines,
+        line_number: usize,
+        line_number_style: Style,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Line<'static>, crate::Error> {
+        let line = self.parser_line(line);
+        if self.plain {
+            let regions = [(self.plain_style(), line.as_ref())];
+            let line = self.to_line(&regions, None, &[], line_number, line_number_style, &mut 0);
+            return Ok(spans::into_owned(line));
+        }
+        let regions = highlighter
+            .highlight_line(&line, syntaxes)
+            .map_err(crate::Error::Highlight)?;
+        let line = self.to_line(&regions, None, &[], line_number, line_number_style, &mut 0);
+        Ok(spans::into_owned(line))
+    }
+
+    /// Highlights a single line and returns the style of the text at `byte_offset`, or `None` if
+    /// the offset is past the end of the line. Line endings are not counted as part of the line.
+    ///
+    /// This is useful for mapping positions from other sources, such as LSP semantic tokens, to
+    /// the colors used by the theme. Like [`highlight_line`](Self::highlight_line), the line is
+    /// parsed using the state left in `highlighter` by the previous line.
+    pub fn style_at(
+        &self,
+        line: &str,
+        byte_offset: usize,
+        highlighter: &mut HighlightLines,
+        syntaxes: &SyntaxSet,
+    ) -> Result<Option<Style>, crate::Error> {
+        let parser_line = self.parser_line(line);
+        let content_len = strip_line_ending(line).len();
+        if self.plain {
+            return Ok(
+                (byte_offset < content_len).then(|| self.syntect_style_to_tui(self.plain_style()))
+            );
+        }
+        let regions = highlighter
+            .highlight_line(&parser_line, syntaxes)
+            .map_err(crate::Error::Highlight)?;
+        if byte_offset >= content_len {
+            return Ok(None);
+        }
+        let mut end = 0;
+        Ok(regions.into_iter().find_map(|(style, text)| {
+            end += text.len();
+            (byte_offset < end).then(|| self.syntect_style_to_tui(style))
+        }))
+    }
+
+    // Wraps text highlighted by this highlighter so it renders with matching settings.
+    pub(crate) fn highlighted_text<'a>(&self, text: Text<'a>) -> HighlightedText<'a> {
+        HighlightedText::new(text, self.get_background_color())
+            .with_east_asian_width(self.east_asian_width)
+            .with_truncate_marker(self.truncate_marker.clone())
+            .with_truncate_marker_style(self.truncate_marker_style())
+    }
+
+    // The marker at the end of truncated lines uses the gutter foreground.
+    fn truncate_marker_style(&self) -> Style {
+        let mut style = Style::new();
+        style.fg = self.get_line_number_style().fg;
+        style
+    }
+
+    // Reads the next line into `line`, replacing invalid UTF-8 if lossy_utf8 is enabled.
+    pub(crate) fn read_line<R>(&self, reader: &mut R, line: &mut String) -> io::Result<usize>
+    where
+        R: BufRead,
+    {
+        if !self.lossy_utf8 {
+            return reader.read_line(line);
+        }
+        let mut bytes = Vec::new();
+        let read = reader.read_until(b'\n', &mut bytes)?;
+        line.push_str(&String::from_utf8_lossy(&bytes));
+        Ok(read)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn is_lossy_utf8(&self) -> bool {
+        self.lossy_utf8
+    }
+
+    pub(crate) fn keeps_final_newline(&self) -> bool {
+        self.keep_final_newline
+    }
+
+    pub(crate) fn is_plain(&self) -> bool {
+        self.plain
+    }
+
+    // Prepares a line to be passed to the parser, which expects every line to end with a newline.
+    pub(crate) fn parser_line<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        if self.preserve_line_endings {
+            with_newline(line)
+        } else {
+            with_lf(line)
+        }
+    }
+
+    // The style used for all code when syntax highlighting is disabled.
+    pub(crate) fn plain_style(&self) -> syntect::highlighting::Style {
+        // An alpha of 1 tells the converter to use the terminal's default color.
+        let default = syntect::highlighting::Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 1,
+        };
+        syntect::highlighting::Style {
+            foreground: self.theme.settings.foreground.unwrap_or(default),
+            background: self.theme.settings.background.unwrap_or(default),
+            font_style: FontStyle::empty(),
+        }
+    }
+
+    fn themes_mut(&mut self) -> impl Iterator<Item = &mut Theme> {
+        std::iter::once(&mut self.theme)
+            .chain(self.syntax_themes.iter_mut().map(|(_, theme)| theme))
+    }
+
+    // Returns the style of the last highlighted range containing the line, if any.
+    pub(crate) fn highlight_row_style(&self, line_number: usize) -> Option<Style> {
+        self.highlight_ranges
+            .iter()
+            .rev()
+            .find(|(range, _)| range.contains(&line_number))
+            .map(|(_, style)| style.unwrap_or(self.highlight_style))
+    }
+
+    pub(crate) fn line_flags(&self, line_number: usize) -> LineFlags {
+        LineFlags {
+            is_current: self.current_line == Some(line_number),
+            is_highlighted: self.highlight_row_style(line_number).is_some(),
+            is_fold_boundary: self.folds.iter().any(|(fold, _)| {
+                !fold.is_empty() && (fold.start == line_number + 1 || fold.end == line_number)
+            }),
+        }
+    }
+
+    // Fits the line number padding to the digits of the largest line number.
+    pub(crate) fn fit_line_numbers(&mut self, total: usize) {
+        // 2 extra spaces for left/right padding
+        self.line_number_padding = total.max(1).ilog10() as usize + 1 + 2;
+    }
+
+    pub(crate) fn bracket_match(&self) -> Option<((usize, usize), Style)> {
+        self.bracket_match
+    }
+
+    // Returns the distance from the current line shown in place of the line number, if relative
+    // line numbers are enabled.
+    pub(crate) fn relative_line_number(&self, line_number: usize) -> Option<usize> {
+        self.relative_line_numbers
+            .map(|current| current.abs_diff(line_number))
+    }
+
+    pub(crate) fn current_match_index(&self) -> Option<usize> {
+        self.current_match.map(|(index, _)| index)
+    }
+
+    // Returns the search query and whether it ignores case, if searching is enabled.
+    pub(crate) fn search(&self) -> Option<(SearchQuery, bool)> {
+        self.search_query
+            .clone()
+            .map(|query| (query, self.search_case_insensitive))
+    }
+
+    // Returns the byte range of `line` covered by the selection, if any. `line` must not include
+    // the line ending.
+    pub(crate) fn selection_range(&self, line_number: usize, line: &str) -> Option<Range<usize>> {
+        let ((start_line, start_column), (end_line, end_column)) = self.selection?;
+        if line_number < start_line || line_number > end_line {
+            return None;
+        }
+        let offset = |column: usize| {
+            line.char_indices()
+                .nth(column)
+                .map_or(line.len(), |(offset, _)| offset)
+        };
+        let start = if line_number == start_line {
+            offset(start_column)
+        } else {
+            0
+        };
+        let end = if line_number == end_line {
+            offset(end_column)
+        } else {
+            line.len()
+        };
+        (start < end).then_some(start..end)
+    }
+
+    // Returns the byte ranges of `line` covered by each highlight span on the line, along with
+    // their styles. `line` must not include the line ending.
+    pub(crate) fn span_ranges(&self, line_number: usize, line: &str) -> Vec<(Range<usize>, Style)> {
+        self.highlight_spans
+            .iter()
+            .filter(|(span_line, _, _)| *span_line == line_number)
+            .filter_map(|(_, columns, style)| {
+                let range = self.column_range_to_bytes(line, columns);
+                (!range.is_empty()).then_some((range, *style))
+            })
+            .collect()
+    }
+
+    // Converts a range of display columns to the byte range of the characters that overlap it.
+    fn column_range_to_bytes(&self, line: &str, columns: &Range<usize>) -> Range<usize> {
+        let mut column: usize = 0;
+        let mut start = None;
+        let mut end = 0;
+        for (offset, c) in line.char_indices() {
+            let width = if c == '\t' {
+                self.tab_width
+                    .saturating_sub(column.checked_rem(self.tab_width).unwrap_or(0))
+            } else {
+                spans::char_width(c, self.east_asian_width)
+            };
+            let overlaps = if width == 0 {
+                columns.contains(&column)
+            } else {
+                column < columns.end && column + width > columns.start
+            };
+            if overlaps {
+                start.get_or_insert(offset);
+                end = offset + c.len_utf8();
+            } else if column >= columns.end {
+                break;
+            }
+            column += width;
+        }
+        start.map_or(0..0, |start| start..end)
+    }
+
+    pub(crate) fn auto_line_number_width_enabled(&self) -> bool {
+        self.auto_line_number_width
+    }
+
+    pub(crate) fn is_folded(&self, line_number: usize) -> bool {
+        self.folds
+            .iter()
+            .any(|(fold, _)| fold.contains(&line_number))
+    }
+
+    // Returns the summary row for the fold starting at the line, unless the fold is nested inside
+    // another fold or has no summary.
+    pub(crate) fn fold_summary(&self, line_number: usize) -> Option<Line<'static>> {
+        let (fold, summary) = self
+            .folds
+            .iter()
+            .find(|(fold, _)| fold.start == line_number && !fold.is_empty())?;
+        let nested = self
+            .folds
+            .iter()
+            .any(|(other, _)| other.start < fold.start && other.contains(&line_number));
+        if nested || summary.is_empty() {
+            return None;
+        }
+        let style = self.get_line_number_style();
+        let gutter = self.get_initial_spans(line_number, style);
+        let gutter_width = spans::spans_width(&gutter, self.east_asian_width);
+        Some(self.apply_background(Line::from_iter([
+            Span::styled(" ".repeat(gutter_width), style),
+            // The summary is drawn over the code area, so it keeps the code background.
+            Span::styled(summary.clone(), Style { bg: None, ..style }),
+        ])))
+    }
+
+    pub(crate) fn tracks_scopes(&self) -> bool {
+        self.with_scopes || !self.scope_styles.is_empty()
+    }
+
+    pub(crate) fn collects_scopes(&self) -> bool {
+        self.with_scopes
+    }
+
+    fn get_initial_spans(
+        &self,
+        line_number: usize,
+        line_number_style: Style,
+    ) -> Vec<Span<'static>> {
+        let separator_style = if self.current_line == Some(line_number) {
+            self.get_current_line_separator_style()
+        } else {
+            self.line_number_separator_style
+                .unwrap_or(line_number_style)
+        };
         // convert 0-based to 1-based
         let line_number = line_number + 1;
         if let Some(template) = &self.gutter_template {
-            return template.0(line_number, line_number_style);
+            let flags = self.line_flags(line_number - 1);
+            let mut spans = template.0(GutterContext {
+                line_number,
+                base_style: line_number_style,
+                is_highlighted: flags.is_highlighted,
+                is_current: flags.is_current,
+            });
+            if let Some((min, max)) = self.gutter_width_bounds {
+                spans = spans::truncate(spans, max, "", self.east_asian_width);
+                let width = spans::spans_width(&spans, self.east_asian_width);
+                if width < min {
+                    spans.push(Span::styled(" ".repeat(min - width), line_number_style));
+                }
+            }
+            return spans;
         }
 
         if self.line_numbers {
-            let line_number = line_number.to_string();
-            let spaces = self
-                .line_number_padding
-                .saturating_sub(line_number.len())
-                // 2 extra spaces for left/right padding
-                .saturating_sub(2);
-            vec![
-                Span::styled(" ".repeat(spaces), line_number_style),
-                Span::styled(line_number, line_number_style),
-                Span::styled(" ", line_number_style),
-                Span::styled(
+            let separator = if let Some(template) = &self.separator_template {
+                Some(template.0(line_number, self.line_flags(line_number - 1)))
+            } else if self.line_number_separator.is_empty() {
+                None
+            } else {
+                Some(Span::styled(
                     self.line_number_separator.clone(),
-                    self.line_number_separator_style
-                        .unwrap_or(line_number_style),
-                ),
-                Span::styled(" ", line_number_style),
-            ]
+                    separator_style,
+                ))
+            };
+            let line_number = match self.relative_line_numbers {
+                Some(current) if current + 1 != line_number => (current + 1).abs_diff(line_number),
+                _ => line_number,
+            }
+            .to_string();
+            let (line_number, spaces) = self.fit_line_number(line_number, separator.as_ref());
+            let (before, after) = match self.line_number_alignment {
+                Alignment::Left => (0, spaces),
+                Alignment::Center => (spaces / 2, spaces - spaces / 2),
+                Alignment::Right => (spaces, 0),
+            };
+            let mut spans = vec![
+                Span::styled(" ".repeat(before), line_number_style),
+                Span::styled(line_number, line_number_style),
+                Span::styled(" ".repeat(after + 1), line_number_style),
+            ];
+            if let Some(separator) = separator {
+                spans.extend([separator, Span::styled(" ", line_number_style)]);
+            }
+            spans
         } else {
             vec![]
         }
     }
 
-    fn to_line(
+    // Returns the line number to display and the number of spaces to align it with, accounting for
+    // the gutter width bounds.
+    fn fit_line_number(&self, line_number: String, separator: Option<&Span>) -> (String, usize) {
+        let digits = spans::str_width(&line_number, self.east_asian_width);
+        // 2 extra spaces for left/right padding
+        let mut column = self.line_number_padding.saturating_sub(2).max(digits);
+        let Some((min, max)) = self.gutter_width_bounds else {
+            return (line_number, column - digits);
+        };
+        // The space after the line number, plus the separator and the space after it.
+        let fixed = 1 + separator.map_or(0, |separator| {
+            spans::str_width(&separator.content, self.east_asian_width) + 1
+        });
+        column = (column + fixed).clamp(min, max).saturating_sub(fixed);
+        if column >= digits {
+            return (line_number, column - digits);
+        }
+        let marker_width = spans::char_width(self.gutter_overflow_marker, self.east_asian_width);
+        if column < marker_width {
+            return (" ".repeat(column), 0);
+        }
+        let mut kept = String::new();
+        let mut width = marker_width;
+        for c in line_number.chars().rev() {
+            let char_width = spans::char_width(c, self.east_asian_width);
+            if width + char_width > column {
+                break;
+            }
+            kept.insert(0, c);
+            width += char_width;
+        }
+        kept.insert(0, self.gutter_overflow_marker);
+        (kept, column - width)
+    }
+
+    // Splits a highlighted line into rows that fit within the wrap width, replacing the gutter
+    // with blank space on continuation rows.
+    pub(crate) fn wrap_line<'a>(&self, mut line: Line<'a>, line_number: usize) -> Vec<Line<'a>> {
+        if self.wrap == WrapMode::None {
+            return vec![line];
+        }
+        let gutter_len = self
+            .get_initial_spans(line_number, self.get_line_number_style())
+            .len()
+            .min(line.spans.len());
+        let code_spans = line.spans.split_off(gutter_len);
+        let gutter_width = spans::spans_width(&line.spans, self.east_asian_width);
+        let available = usize::from(self.wrap_width).saturating_sub(gutter_width);
+        let rows = spans::wrap(
+            code_spans,
+            available,
+            self.wrap == WrapMode::Word,
+            self.east_asian_width,
+        );
+        let blank_gutter = Span::styled(
+            " ".repeat(gutter_width),
+            line.spans
+                .first()
+                .map_or_else(|| self.get_line_number_style(), |span| span.style),
+        );
+        let mut gutter = Some(line.spans);
+        rows.into_iter()
+            .map(|row| {
+                let mut spans = gutter.take().unwrap_or_else(|| vec![blank_gutter.clone()]);
+                spans.extend(row);
+                Line {
+                    spans,
+                    style: line.style,
+                    alignment: line.alignment,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_line<'a>(
         &self,
-        v: &[(syntect::highlighting::Style, &str)],
+        v: &[(syntect::highlighting::Style, &'a str)],
+        scopes: Option<&[ScopeStack]>,
+        patches: &[(Range<usize>, Style)],
         line_number: usize,
         line_number_style: Style,
-    ) -> Line<'static> {
-        let mut spans = self.get_initial_spans(line_number, line_number_style);
-        let highlight_row = self
-            .highlight_ranges
+        match_index: &mut usize,
+    ) -> Line<'a> {
+        let mut spans: Vec<Span<'a>> = self.get_initial_spans(line_number, line_number_style);
+        let gutter_len = spans.len();
+        let current_row = self.current_line == Some(line_number);
+        let highlight_style = self.highlight_row_style(line_number);
+        let highlight_row = highlight_style.is_some();
+        let highlight_style = highlight_style.unwrap_or_default();
+
+        // The line ending may be split across regions, so it's found from the end of the line.
+        let ending: String = v
             .iter()
-            .any(|r| r.contains(&line_number));
+            .rev()
+            .flat_map(|(_, text)| text.chars().rev())
+            .take(2)
+            .collect();
+        let ending_len = match ending.as_str() {
+            "\n\r" => 2,
+            _ if ending.starts_with('\n') => 1,
+            _ => 0,
+        };
+        let mut remaining = v.iter().map(|(_, text)| text.len()).sum::<usize>() - ending_len;
 
-        for &(ref style, mut text) in v {
-            let ends_with_newline = text.ends_with('\n');
-            if ends_with_newline {
-                text = &text[..text.len() - 1];
-            }
+        let mut code_spans = Vec::with_capacity(v.len());
+        for (i, &(ref style, mut text)) in v.iter().enumerate() {
+            text = &text[..text.len().min(remaining)];
+            remaining -= text.len();
 
             let mut tui_style = self.syntect_style_to_tui(*style);
+            if let Some(scopes) = scopes {
+                tui_style = self.apply_scope_styles(tui_style, &scopes[i]);
+            }
+            if current_row {
+                tui_style = tui_style.patch(self.get_current_line_style());
+            }
             if highlight_row {
-                tui_style = tui_style.patch(self.highlight_style);
+                tui_style = tui_style.patch(highlight_style);
             }
 
-            spans.push(Span::styled(text.to_string(), tui_style));
+            code_spans.push(Span::styled(text, tui_style));
+        }
+        if let Some(style) = self.link_style {
+            let text: String = code_spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect();
+            let links: Vec<_> = find_urls(&text)
+                .into_iter()
+                .map(|range| (range, style))
+                .collect();
+            code_spans = spans::patch_ranges(code_spans, &links);
+        }
+        if let Some(query) = &self.search_query {
+            let matches = self.search_matches(&code_spans, query, match_index);
+            code_spans = spans::patch_ranges(code_spans, &matches);
+        }
+        code_spans = spans::patch_ranges(code_spans, patches);
+        if self.tab_width > 0 {
+            code_spans = spans::expand_tabs(code_spans, self.tab_width, self.east_asian_width);
+        }
+        if let Some(style) = self.trailing_whitespace_style {
+            code_spans =
+                spans::split_trailing_whitespace(code_spans, style, self.show_whitespace_on_blank);
+        }
+        if let Some(max_spans) = self.max_spans_per_line {
+            code_spans = spans::cap(spans::coalesce(code_spans), max_spans);
+        }
+        let code_width = spans::spans_width(&code_spans, self.east_asian_width);
+        let padding = usize::from(self.min_content_width).saturating_sub(code_width);
+        if padding > 0 {
+            code_spans.push(Span::raw(" ".repeat(padding)));
+        }
+        spans.extend(code_spans);
+
+        // The background is set once on the line, so spans only need to carry a background
+        // where it differs.
+        let background = self.get_background_color();
+        let gutter_background = self.get_gutter_background();
+        let mut row_style = Style::new();
+        if let Some((even, odd)) = self.zebra_stripe {
+            // Line numbers are 1-based, so the first line is odd.
+            row_style = row_style.patch(if line_number.is_multiple_of(2) {
+                odd
+            } else {
+                even
+            });
+        }
+        if current_row {
+            row_style = row_style.patch(self.get_current_line_style());
+        }
+        let row_covers_gutter = current_row || self.zebra_stripe.is_some();
+        for (i, span) in spans.iter_mut().enumerate() {
+            if highlight_row && i < gutter_len {
+                // Highlighted ranges don't cover the gutter, so keep an explicit background to stop
+                // the highlighted line style from showing through.
+                if self.highlight_code_only {
+                    span.style = span.style.remove_modifier(highlight_style.add_modifier);
+                }
+                if row_covers_gutter && span.style.bg == gutter_background {
+                    span.style.bg = row_style.bg.or(span.style.bg);
+                }
+                span.style.bg = span.style.bg.or(background);
+            } else if span.style.bg == background
+                || (i < gutter_len && row_covers_gutter && span.style.bg == gutter_background)
+            {
+                // Row styles cover the gutter as well.
+                span.style.bg = None;
+            }
         }
 
-        let mut line = Line::from_iter(spans);
+        // The row styles are applied to the line after the background so the widget can tell
+        // which rows need to be filled to the full width when rendering.
+        let mut line = self
+            .apply_background(Line::from_iter(spans))
+            .patch_style(row_style);
         if highlight_row {
-            line = line.patch_style(self.highlight_style);
+            line = line.patch_style(highlight_style);
         }
-        self.apply_background(line)
+        self.remove_colors(line)
+    }
+
+    // Counts the search matches in a line that isn't being rendered, so the current match can
+    // still be found in the lines that follow.
+    pub(crate) fn count_matches(&self, line: &str) -> usize {
+        self.search_query.as_ref().map_or(0, |query| {
+            query.find(line, self.search_case_insensitive).len()
+        })
+    }
+
+    fn search_matches(
+        &self,
+        code_spans: &[Span<'_>],
+        query: &SearchQuery,
+        match_index: &mut usize,
+    ) -> Vec<(Range<usize>, Style)> {
+        let text: String = code_spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        query
+            .find(&text, self.search_case_insensitive)
+            .into_iter()
+            .map(|range| {
+                let style = match self.current_match {
+                    Some((index, style)) if index == *match_index => style,
+                    _ => self.search_style,
+                };
+                *match_index += 1;
+                (range, style)
+            })
+            .collect()
+    }
+
+    fn apply_scope_styles(&self, style: Style, scopes: &ScopeStack) -> Style {
+        let mut matches: Vec<_> = self
+            .scope_styles
+            .iter()
+            .filter(|scope_style| {
+                scope_style
+                    .filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.does_match(scopes.as_slice()).is_some())
+            })
+            .filter_map(|scope_style| {
+                scope_style
+                    .selectors
+                    .does_match(scopes.as_slice())
+                    .map(|power| (power, scope_style.style))
+            })
+            .collect();
+        // Patch in order of increasing specificity so the closest match wins.
+        matches.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        matches
+            .into_iter()
+            .fold(style, |style, (_, scope_style)| style.patch(scope_style))
     }
 
     fn adapt_style(&self, style: Style) -> Style {
@@ -342,22 +2866,30 @@ impl Highlighter {
         return Some(color);
     }
 
-    fn apply_background<'a, S>(&self, item: S) -> S
-    where
-        S: Stylize<'a, S>,
-    {
-        if let Some(bg) = self.override_background {
-            return item.bg(bg);
+    // Every row passes through here, so monochrome mode is applied here as well.
+    fn apply_background<'a>(&self, line: Line<'a>) -> Line<'a> {
+        let line = match self.get_background_color() {
+            Some(bg) => line.bg(bg),
+            None => line,
         };
-        if let Some(bg) = self
-            .theme
-            .settings
-            .background
-            .and_then(|bg| self.converter.syntect_color_to_tui(bg))
-        {
-            return item.bg(bg);
+        self.remove_colors(line)
+    }
+
+    // Strips all colors from the row when monochrome mode is enabled, keeping the modifiers.
+    fn remove_colors<'a>(&self, mut line: Line<'a>) -> Line<'a> {
+        if !self.monochrome {
+            return line;
+        }
+        let colorless = |style: Style| Style {
+            fg: None,
+            bg: None,
+            ..style
+        };
+        line.style = colorless(line.style);
+        for span in &mut line.spans {
+            span.style = colorless(span.style);
         }
-        item
+        line
     }
 
     fn syntect_style_to_tui(
@@ -366,9 +2898,100 @@ impl Highlighter {
     ) -> ratatui_core::style::Style {
         let mut tui_style = self.converter.syntect_style_to_tui(style);
 
-        if let Some(bg) = self.override_background {
-            tui_style = tui_style.bg(bg);
+        match self.override_background {
+            OverrideBackground::Color(bg) => tui_style = tui_style.bg(bg),
+            OverrideBackground::None => tui_style.bg = None,
+            OverrideBackground::Theme => {}
         }
         tui_style
     }
 }
+
+// The brightness of the default gutter background, as a percentage of the code background.
+const GUTTER_SHADE: u16 = 85;
+
+// Returns the indentation width of the line, or None if the line is blank.
+fn indentation(line: &str) -> Option<usize> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    Some(
+        line.chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum(),
+    )
+}
+
+// Finds the byte ranges of http and https URLs in `text`.
+fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = ["http://", "https://"]
+        .iter()
+        .filter_map(|scheme| text[offset..].find(scheme))
+        .min()
+    {
+        let start = offset + start;
+        let len = text[start..]
+            .find(|c: char| c.is_whitespace() || "\"'`<>()[]{}".contains(c))
+            .unwrap_or(text.len() - start);
+        let url = text[start..start + len].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        let end = start + url.len();
+        if !url.ends_with("//") {
+            urls.push(start..end);
+        }
+        offset = start + len.max(1);
+    }
+    urls
+}
+
+// Finds the byte ranges of non-overlapping occurrences of `query` in `text`. When ignoring case,
+// characters are compared by their lowercase forms, so a match may have a different byte length
+// than the query.
+fn find_query(text: &str, query: &str, case_insensitive: bool) -> Vec<Range<usize>> {
+    if !case_insensitive {
+        return text
+            .match_indices(query)
+            .map(|(start, found)| start..start + found.len())
+            .collect();
+    }
+    let query: Vec<_> = query.chars().flat_map(char::to_lowercase).collect();
+    let mut matches = Vec::new();
+    let mut search_from = 0;
+    for (start, _) in text.char_indices() {
+        if start < search_from {
+            continue;
+        }
+        let mut lowered = text[start..]
+            .char_indices()
+            .flat_map(|(i, c)| c.to_lowercase().map(move |lower| (i, c, lower)));
+        let mut end = None;
+        for (i, expected) in query.iter().enumerate() {
+            match lowered.next() {
+                Some((offset, c, lower)) if lower == *expected => {
+                    if i == query.len() - 1 {
+                        end = Some(start + offset + c.len_utf8());
+                    }
+                }
+                _ => break,
+            }
+        }
+        if let Some(end) = end {
+            matches.push(start..end);
+            search_from = end;
+        }
+    }
+    matches
+}
+
+// Converts a one-based, end-exclusive range of character columns into a byte range in `line`.
+fn char_range_to_bytes(line: &str, columns: &Range<usize>) -> Range<usize> {
+    let byte_offset = |column: usize| {
+        line.char_indices()
+            .nth(column.saturating_sub(1))
+            .map_or(line.len(), |(i, _)| i)
+    };
+    let start = byte_offset(columns.start);
+    start..byte_offset(columns.end).max(start)
+}
@@ -1,19 +1,69 @@
 #![warn(missing_docs, missing_debug_implementations)]
 #![forbid(clippy::unwrap_used)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
-#![doc = include_str!("../README.md")]
+#![cfg_attr(feature = "ratatui", doc = include_str!("../README.md"))]
+#![cfg_attr(
+    not(feature = "ratatui"),
+    doc = "Syntax highlighting with [`syntect`], rendered as plain data through the [`render`] \
+           module."
+)]
 
+#[cfg(feature = "ratatui")]
+mod brackets;
+mod compiler_error;
 mod convert;
+#[cfg(feature = "ratatui")]
+pub mod gutter;
+#[cfg(feature = "ratatui")]
+mod highlight_iter;
+#[cfg(feature = "ratatui")]
+mod highlighted_text;
+#[cfg(feature = "ratatui")]
 mod highlighter;
+#[cfg(feature = "ratatui")]
+mod line_cache;
+#[cfg(feature = "ratatui")]
+mod line_highlighter;
+pub mod render;
+#[cfg(feature = "ratatui")]
+mod spans;
+#[cfg(feature = "plist-load")]
+mod theme;
+#[cfg(feature = "ratatui")]
+mod transcript;
 
 use std::fmt::{self, Display};
 use std::io;
 
+pub use compiler_error::*;
 pub use convert::*;
+#[cfg(feature = "encoding_rs")]
+pub use encoding_rs;
+#[cfg(feature = "tokio")]
+pub use futures_util;
+#[cfg(feature = "ratatui")]
+pub use glob;
+#[cfg(feature = "ratatui")]
+pub use highlight_iter::*;
+#[cfg(feature = "ratatui")]
+pub use highlighted_text::*;
+#[cfg(feature = "ratatui")]
 pub use highlighter::*;
+#[cfg(feature = "ratatui")]
+pub use line_cache::*;
+#[cfg(feature = "ratatui")]
+pub use line_highlighter::*;
+#[cfg(feature = "regex")]
+pub use regex;
 pub use syntect;
 #[cfg(feature = "termprofile")]
 pub use termprofile;
+#[cfg(feature = "plist-load")]
+pub use theme::*;
+#[cfg(feature = "tokio")]
+pub use tokio;
+#[cfg(feature = "ratatui")]
+pub use transcript::*;
 
 /// Error returned from the syntax highlighter.
 #[derive(Debug)]
@@ -22,6 +72,14 @@ pub enum Error {
     Read(io::Error),
     /// Error highlighting content.
     Highlight(syntect::Error),
+    /// The theme is missing settings required for highlighting.
+    InvalidTheme(&'static str),
+    /// Error loading a theme.
+    Theme(syntect::LoadingError),
+    /// No syntax could be detected and no fallback syntax is available.
+    UnknownSyntax,
+    /// A scope selector couldn't be parsed.
+    ScopeSelector(syntect::parsing::ParseScopeError),
 }
 
 impl std::error::Error for Error {}
@@ -31,6 +89,10 @@ impl Display for Error {
         match self {
             Self::Read(e) => write!(f, "error reading from source: {e:?}"),
             Self::Highlight(e) => write!(f, "error highlighting content: {e:?}"),
+            Self::InvalidTheme(e) => write!(f, "invalid theme: {e}"),
+            Self::Theme(e) => write!(f, "error loading theme: {e:?}"),
+            Self::UnknownSyntax => write!(f, "no syntax found for content"),
+            Self::ScopeSelector(e) => write!(f, "invalid scope selector: {e:?}"),
         }
     }
 }
@@ -0,0 +1,116 @@
+use std::fmt::{self, Debug, Formatter};
+use std::ops::Range;
+
+use ratatui_core::text::Line;
+
+use crate::highlighter::SearchQuery;
+use crate::line_highlighter::{LineState, RowKey};
+
+/// Caches the highlighted output and parse state of each line in a buffer, so an edit only
+/// requires highlighting the lines from the edit onward.
+///
+/// This is intended for editors that re-highlight on every keystroke. Pass it to
+/// [`Highlighter::highlight_with_cache`](crate::Highlighter::highlight_with_cache) along with the
+/// first line that changed since the previous call. A cache should only be used with one syntax
+/// and highlighter configuration. Call [`clear`](Self::clear) if either of those change.
+///
+/// Options that follow the cursor, such as the current line, selection, matching brackets,
+/// relative line numbers, and search results, can change between calls without clearing the
+/// cache. Only the rows they affect are highlighted again.
+#[derive(Clone, Default)]
+pub struct LineCache {
+    entries: Vec<CacheEntry>,
+    // The line number padding used for the cached rows.
+    line_number_padding: usize,
+    // The search used to count the matches stored in each line state.
+    search: Option<(SearchQuery, bool)>,
+}
+
+#[derive(Clone)]
+struct CacheEntry {
+    rows: Vec<Line<'static>>,
+    // The state after highlighting the line.
+    state: LineState,
+    key: RowKey,
+}
+
+impl Debug for LineCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineCache")
+            .field("len", &self.entries.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl LineCache {
+    /// Creates an empty [`LineCache`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of lines in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the cache contains no lines.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all lines from the cache.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn invalidate(&mut self, from: usize) {
+        self.entries.truncate(from);
+    }
+
+    // Discards every row if the gutter width or search changed, since those affect every row and
+    // the match counts stored in each line state.
+    pub(crate) fn sync(&mut self, line_number_padding: usize, search: Option<(SearchQuery, bool)>) {
+        if line_number_padding != self.line_number_padding || search != self.search {
+            self.entries.clear();
+            self.line_number_padding = line_number_padding;
+            self.search = search;
+        }
+    }
+
+    pub(crate) fn last_state(&self) -> Option<&LineState> {
+        self.entries.last().map(|entry| &entry.state)
+    }
+
+    // Returns the state at the start of the line, or `None` for the first line.
+    pub(crate) fn state_before(&self, line: usize) -> Option<&LineState> {
+        line.checked_sub(1)
+            .and_then(|previous| self.entries.get(previous))
+            .map(|entry| &entry.state)
+    }
+
+    // Returns the indices of the search matches in the line.
+    pub(crate) fn matches(&self, line: usize) -> Range<usize> {
+        let start = self.state_before(line).map_or(0, LineState::match_index);
+        start..self.entries[line].state.match_index()
+    }
+
+    pub(crate) fn key(&self, line: usize) -> &RowKey {
+        &self.entries[line].key
+    }
+
+    pub(crate) fn push(&mut self, rows: Vec<Line<'static>>, state: LineState, key: RowKey) {
+        self.entries.push(CacheEntry { rows, state, key });
+    }
+
+    // Replaces the rows of a line whose decorations changed. The line state is unchanged, since
+    // the text of the line is the same.
+    pub(crate) fn replace(&mut self, line: usize, rows: Vec<Line<'static>>, key: RowKey) {
+        let entry = &mut self.entries[line];
+        entry.rows = rows;
+        entry.key = key;
+    }
+
+    pub(crate) fn rows(&self) -> impl Iterator<Item = &Line<'static>> {
+        self.entries.iter().flat_map(|entry| &entry.rows)
+    }
+}
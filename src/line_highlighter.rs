@@ -0,0 +1,455 @@
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Formatter};
+use std::ops::Range;
+
+use ratatui_core::style::Style;
+use ratatui_core::text::Line;
+use syntect::highlighting::{HighlightState, RangedHighlightIterator};
+use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+
+use crate::render::strip_line_ending;
+use crate::{Highlighter, LineCache, LineFlags, ScopedSpan, brackets, spans};
+
+type StyledRange = (syntect::highlighting::Style, Range<usize>);
+
+/// Highlights lines one at a time, carrying the parse state over from the previous line.
+///
+/// This is useful for streaming content such as a `tail -f`-style view where new lines are
+/// appended over time and re-highlighting the previous content would be wasteful.
+/// Create one using [`Highlighter::line_highlighter`].
+pub struct LineHighlighter<'a> {
+    highlighter: Cow<'a, Highlighter>,
+    syntaxes: &'a SyntaxSet,
+    theme_highlighter: syntect::highlighting::Highlighter<'a>,
+    parse_state: ParseState,
+    highlight_state: HighlightState,
+    line_number_style: Style,
+    line_number: usize,
+    match_index: usize,
+    ends_with_newline: bool,
+    brackets: Vec<(usize, usize)>,
+}
+
+// The parse state left after highlighting a line, which can be restored to continue highlighting
+// from that point.
+#[derive(Clone, Debug)]
+pub(crate) struct LineState {
+    parse_state: ParseState,
+    highlight_state: HighlightState,
+    line_number: usize,
+    match_index: usize,
+    ends_with_newline: bool,
+}
+
+// The decorations of a line that depend on its position or the highlighter's options rather than
+// its text. A cached row is up to date as long as its text and key haven't changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RowKey {
+    flags: LineFlags,
+    highlight_style: Option<Style>,
+    folded: bool,
+    relative_number: Option<usize>,
+    patches: Vec<(Range<usize>, Style)>,
+    current_match: bool,
+}
+
+impl LineState {
+    pub(crate) fn match_index(&self) -> usize {
+        self.match_index
+    }
+}
+
+impl Debug for LineHighlighter<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineHighlighter")
+            .field("highlighter", &self.highlighter)
+            .field("line_number_style", &self.line_number_style)
+            .field("line_number", &self.line_number)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> LineHighlighter<'a> {
+    pub(crate) fn new(
+        highlighter: &'a Highlighter,
+        syntax: &SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+    ) -> Self {
+        let theme = highlighter.syntax_theme(syntax);
+        let highlighter = highlighter.for_syntax(syntax);
+        let line_number_style = highlighter.get_line_number_style();
+        let theme_highlighter = syntect::highlighting::Highlighter::new(theme);
+        let highlight_state = HighlightState::new(&theme_highlighter, ScopeStack::new());
+        Self {
+            highlighter,
+            syntaxes,
+            theme_highlighter,
+            parse_state: ParseState::new(syntax),
+            highlight_state,
+            line_number_style,
+            line_number: 0,
+            match_index: 0,
+            ends_with_newline: false,
+            brackets: Vec::new(),
+        }
+    }
+
+    // Sizes the line numbers to fit `total` lines. Has no effect unless auto_line_number_width is
+    // enabled or if the total is unknown (0).
+    pub(crate) fn with_total_lines(mut self, total: usize) -> Self {
+        if self.highlighter.auto_line_number_width_enabled() && total > 0 {
+            self.highlighter.to_mut().fit_line_numbers(total);
+        }
+        self
+    }
+
+    // Finds the brackets to highlight if bracket matching is enabled. `lines` must contain the
+    // whole source.
+    pub(crate) fn with_brackets(
+        mut self,
+        lines: &[&str],
+        syntax: &SyntaxReference,
+    ) -> Result<Self, crate::Error> {
+        if let Some((cursor, _)) = self.highlighter.bracket_match() {
+            self.brackets = brackets::find_match(lines, cursor, syntax, self.syntaxes)?
+                .map(Vec::from)
+                .unwrap_or_default();
+        }
+        Ok(self)
+    }
+
+    // Finds the brackets to highlight like with_brackets, but starts parsing from the states
+    // stored in `cache` rather than the start of the source.
+    pub(crate) fn with_cached_brackets(
+        mut self,
+        lines: &[&str],
+        syntax: &SyntaxReference,
+        cache: &LineCache,
+    ) -> Result<Self, crate::Error> {
+        if self.highlighter.is_plain() {
+            // The cached states aren't parsed when syntax highlighting is disabled.
+            return self.with_brackets(lines, syntax);
+        }
+        if let Some((cursor, _)) = self.highlighter.bracket_match() {
+            let state_at = |line| {
+                cache.state_before(line).map_or_else(
+                    || (ParseState::new(syntax), ScopeStack::new()),
+                    |state| {
+                        (
+                            state.parse_state.clone(),
+                            state.highlight_state.path.clone(),
+                        )
+                    },
+                )
+            };
+            self.brackets =
+                brackets::find_match_from(lines, cursor, self.syntaxes, cache.len(), state_at)?
+                    .map(Vec::from)
+                    .unwrap_or_default();
+        }
+        Ok(self)
+    }
+
+    pub(crate) fn highlighter(&self) -> &Highlighter {
+        &self.highlighter
+    }
+
+    /// Highlights the next line, continuing from the state left by the previous call.
+    pub fn push(&mut self, line: &str) -> Result<Line<'static>, crate::Error> {
+        self.push_borrowed(line).map(spans::into_owned)
+    }
+
+    // Highlights the next line, borrowing the text of each span from the input.
+    pub(crate) fn push_borrowed<'b>(&mut self, line: &'b str) -> Result<Line<'b>, crate::Error> {
+        self.push_scoped(line).map(|(line, _)| line)
+    }
+
+    // Highlights the next line, patching `extra` styles over byte ranges of the line. The
+    // patches are applied before tabs are expanded, so the ranges refer to the source text.
+    pub(crate) fn push_patched(
+        &mut self,
+        line: &str,
+        extra: &[(Range<usize>, Style)],
+    ) -> Result<Line<'static>, crate::Error> {
+        self.push_scoped_patched(line, extra)
+            .map(|(line, _)| spans::into_owned(line))
+    }
+
+    // Highlights the next line, also returning the scopes of each region if the highlighter
+    // collects them.
+    pub(crate) fn push_scoped<'b>(
+        &mut self,
+        line: &'b str,
+    ) -> Result<(Line<'b>, Vec<ScopedSpan>), crate::Error> {
+        self.push_scoped_patched(line, &[])
+    }
+
+    fn push_scoped_patched<'b>(
+        &mut self,
+        line: &'b str,
+        extra: &[(Range<usize>, Style)],
+    ) -> Result<(Line<'b>, Vec<ScopedSpan>), crate::Error> {
+        self.ends_with_newline = line.ends_with('\n');
+        let (regions, scopes) = self.regions(&self.highlighter.parser_line(line))?;
+        // The regions are computed over a copy of the line with a normalized line ending, so
+        // they're clamped to the content of the original input.
+        let line = strip_line_ending(line);
+        let regions: Vec<_> = regions
+            .into_iter()
+            .map(|(style, range)| {
+                (
+                    style,
+                    range.start.min(line.len())..range.end.min(line.len()),
+                )
+            })
+            .collect();
+        let scoped_spans = match &scopes {
+            Some(scopes) if self.highlighter.collects_scopes() => regions
+                .iter()
+                .zip(scopes)
+                .filter(|((_, range), _)| !range.is_empty())
+                .map(|((_, range), scopes)| ScopedSpan {
+                    range: range.clone(),
+                    scope: scope_string(scopes),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let regions: Vec<_> = regions
+            .into_iter()
+            .map(|(style, range)| (style, &line[range]))
+            .collect();
+        let patches = self.patches(self.line_number, line, extra);
+        let highlighted = self.highlighter.to_line(
+            &regions,
+            scopes.as_deref(),
+            &patches,
+            self.line_number,
+            self.line_number_style,
+            &mut self.match_index,
+        );
+        self.line_number += 1;
+        Ok((highlighted, scoped_spans))
+    }
+
+    // Collects the styles patched over the line from the selection, highlighted spans, and
+    // matching brackets. `line` must not include the line ending.
+    fn patches(
+        &self,
+        line_number: usize,
+        line: &str,
+        extra: &[(Range<usize>, Style)],
+    ) -> Vec<(Range<usize>, Style)> {
+        let mut patches: Vec<_> = self
+            .highlighter
+            .selection_range(line_number, line)
+            .map(|range| (range, self.highlighter.get_selection_style()))
+            .into_iter()
+            .collect();
+        patches.extend(self.highlighter.span_ranges(line_number, line));
+        patches.extend(extra.iter().cloned());
+        // Brackets are patched after the selection so they stand out inside of it.
+        if let Some((_, style)) = self.highlighter.bracket_match() {
+            patches.extend(
+                self.brackets
+                    .iter()
+                    .filter(|(bracket_line, _)| *bracket_line == line_number)
+                    .map(|(_, offset)| (*offset..*offset + 1, style)),
+            );
+        }
+        patches
+    }
+
+    // Returns the key used to check whether a cached row for the line is up to date. `matches`
+    // are the indices of the search matches in the line.
+    pub(crate) fn row_key(&self, line_number: usize, line: &str, matches: Range<usize>) -> RowKey {
+        let highlighter = &self.highlighter;
+        RowKey {
+            flags: highlighter.line_flags(line_number),
+            highlight_style: highlighter.highlight_row_style(line_number),
+            folded: highlighter.is_folded(line_number),
+            relative_number: highlighter.relative_line_number(line_number),
+            patches: self.patches(line_number, strip_line_ending(line), &[]),
+            current_match: highlighter
+                .current_match_index()
+                .is_some_and(|index| matches.contains(&index)),
+        }
+    }
+
+    // Returns the number of search matches found so far.
+    pub(crate) fn match_index(&self) -> usize {
+        self.match_index
+    }
+
+    // Parses the next line into styled byte ranges, along with the scopes for each range if the
+    // highlighter needs them.
+    fn regions(
+        &mut self,
+        line: &str,
+    ) -> Result<(Vec<StyledRange>, Option<Vec<ScopeStack>>), crate::Error> {
+        if self.highlighter.is_plain() {
+            return Ok((vec![(self.highlighter.plain_style(), 0..line.len())], None));
+        }
+        let ops = self
+            .parse_state
+            .parse_line(line, self.syntaxes)
+            .map_err(|e| crate::Error::Highlight(e.into()))?;
+        let initial_scopes = self
+            .highlighter
+            .tracks_scopes()
+            .then(|| self.highlight_state.path.clone());
+        let regions: Vec<_> = RangedHighlightIterator::new(
+            &mut self.highlight_state,
+            &ops,
+            line,
+            &self.theme_highlighter,
+        )
+        .collect();
+        let scopes = initial_scopes
+            .map(|scopes| region_scopes(scopes, &ops, &regions))
+            .transpose()?;
+        let regions = regions
+            .into_iter()
+            .map(|(style, _, range)| (style, range))
+            .collect();
+        Ok((regions, scopes))
+    }
+
+    // Advances the parse state past the next line without producing any output.
+    pub(crate) fn skip(&mut self, line: &str) -> Result<(), crate::Error> {
+        self.ends_with_newline = line.ends_with('\n');
+        self.match_index += self.highlighter.count_matches(strip_line_ending(line));
+        let line = self.highlighter.parser_line(line);
+        self.line_number += 1;
+        if self.highlighter.is_plain() {
+            return Ok(());
+        }
+        let ops = self
+            .parse_state
+            .parse_line(&line, self.syntaxes)
+            .map_err(|e| crate::Error::Highlight(e.into()))?;
+        // The highlight state is only updated as the regions are iterated.
+        RangedHighlightIterator::new(
+            &mut self.highlight_state,
+            &ops,
+            &line,
+            &self.theme_highlighter,
+        )
+        .for_each(drop);
+        Ok(())
+    }
+
+    // Highlights the next line, returning None if the line is hidden by a fold. The first line of
+    // a fold is replaced with the fold's summary row, if it has one.
+    pub(crate) fn push_visible(
+        &mut self,
+        line: &str,
+    ) -> Result<Option<Line<'static>>, crate::Error> {
+        let line_number = self.line_number;
+        let folded = self.highlighter.is_folded(line_number);
+        let line = self.push(line)?;
+        if folded {
+            return Ok(self.highlighter.fold_summary(line_number));
+        }
+        Ok(Some(line))
+    }
+
+    // Highlights the next line and wraps it into rows. Returns no rows if the line is hidden by a
+    // fold, other than the summary row at the start of the fold.
+    pub(crate) fn push_rows(&mut self, line: &str) -> Result<Vec<Line<'static>>, crate::Error> {
+        Ok(self
+            .push_rows_borrowed(line)?
+            .into_iter()
+            .map(spans::into_owned)
+            .collect())
+    }
+
+    // Like push_rows, but borrows the text of each span from the input.
+    pub(crate) fn push_rows_borrowed<'b>(
+        &mut self,
+        line: &'b str,
+    ) -> Result<Vec<Line<'b>>, crate::Error> {
+        let line_number = self.line_number;
+        let folded = self.highlighter.is_folded(line_number);
+        let line = self.push_borrowed(line)?;
+        if folded {
+            return Ok(self
+                .highlighter
+                .fold_summary(line_number)
+                .into_iter()
+                .collect());
+        }
+        Ok(self.highlighter.wrap_line(line, line_number))
+    }
+
+    pub(crate) fn set_line_number(&mut self, line_number: usize) {
+        self.line_number = line_number;
+    }
+
+    // Returns true if the last line that was pushed ended with a newline.
+    pub(crate) fn ends_with_newline(&self) -> bool {
+        self.ends_with_newline
+    }
+
+    // Emits the empty final line if the source ended with a newline and the highlighter is
+    // configured to keep it.
+    pub(crate) fn finish(&mut self) -> Result<Option<Line<'static>>, crate::Error> {
+        if self.highlighter.keeps_final_newline() && self.ends_with_newline {
+            self.push_visible("")
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(crate) fn state(&self) -> LineState {
+        LineState {
+            parse_state: self.parse_state.clone(),
+            highlight_state: self.highlight_state.clone(),
+            line_number: self.line_number,
+            match_index: self.match_index,
+            ends_with_newline: self.ends_with_newline,
+        }
+    }
+
+    pub(crate) fn restore(&mut self, state: &LineState) {
+        self.parse_state = state.parse_state.clone();
+        self.highlight_state = state.highlight_state.clone();
+        self.line_number = state.line_number;
+        self.match_index = state.match_index;
+        self.ends_with_newline = state.ends_with_newline;
+    }
+
+    /// Returns the number of lines that have been highlighted so far.
+    pub fn line_count(&self) -> usize {
+        self.line_number
+    }
+}
+
+fn scope_string(scopes: &ScopeStack) -> String {
+    scopes
+        .as_slice()
+        .iter()
+        .map(|scope| scope.build_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Replays the parser's scope operations to find the scope stack that applies to each region.
+fn region_scopes<S, T>(
+    mut scopes: ScopeStack,
+    ops: &[(usize, ScopeStackOp)],
+    regions: &[(S, T, Range<usize>)],
+) -> Result<Vec<ScopeStack>, crate::Error> {
+    let mut ops = ops.iter().peekable();
+    regions
+        .iter()
+        .map(|(_, _, range)| {
+            while let Some((_, op)) = ops.next_if(|(pos, _)| *pos <= range.start) {
+                scopes
+                    .apply(op)
+                    .map_err(|e| crate::Error::Highlight(e.into()))?;
+            }
+            Ok(scopes.clone())
+        })
+        .collect()
+}
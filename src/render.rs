@@ -0,0 +1,309 @@
+//! Highlighted output as plain data structures for renderers that don't use ratatui.
+//!
+//! The types in this module only depend on [`syntect`], so they can be used to drive other
+//! rendering surfaces (such as a GPU text renderer) while still sharing this crate's handling of
+//! theme colors. A [`Converter`] can be used to quantize the colors for terminals with limited
+//! color support. Conversions into ratatui types are provided on top when the `ratatui` feature
+//! is enabled.
+//!
+//! [`to_html`] and [`to_ansi_string`] can be used to render the output without a terminal UI,
+//! such as when generating highlighted snippets on a server.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+#[cfg(feature = "ratatui")]
+use ratatui_core::style::{Color, Modifier, Style};
+#[cfg(feature = "ratatui")]
+use ratatui_core::text::Span;
+use syntect::highlighting::{FontStyle, Theme};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::Converter;
+
+/// A single line of highlighted output.
+pub type StyledLine = Vec<StyledRun>;
+
+/// A color used in a [`StyledRun`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RunColor {
+    /// An ANSI color index. Indices `0` through `15` are the standard terminal colors.
+    Indexed(u8),
+    /// A 24-bit RGB color.
+    Rgb(u8, u8, u8),
+}
+
+impl RunColor {
+    /// Converts a syntect [`Color`](syntect::highlighting::Color) into a [`RunColor`], accounting
+    /// for the special encodings used by ANSI themes. Returns [`None`] if the color should use the
+    /// terminal's default.
+    pub fn from_syntect(color: syntect::highlighting::Color) -> Option<Self> {
+        match color.a {
+            0 => Some(Self::Indexed(color.r)),
+            1 => None,
+            _ => Some(Self::Rgb(color.r, color.g, color.b)),
+        }
+    }
+
+    /// Returns the RGB value of the color. Indexed colors are resolved using the standard xterm
+    /// palette.
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Self::Rgb(r, g, b) => (r, g, b),
+            Self::Indexed(index @ 0..16) => ANSI16_PALETTE[usize::from(index)],
+            Self::Indexed(index @ 16..232) => {
+                let index = usize::from(index - 16);
+                (
+                    CUBE_LEVELS[index / 36],
+                    CUBE_LEVELS[index / 6 % 6],
+                    CUBE_LEVELS[index % 6],
+                )
+            }
+            Self::Indexed(index) => {
+                let gray = 8 + (index - 232) * 10;
+                (gray, gray, gray)
+            }
+        }
+    }
+}
+
+// The xterm palette for the 16 basic colors.
+pub(crate) const ANSI16_PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0x80, 0x00, 0x00),
+    (0x00, 0x80, 0x00),
+    (0x80, 0x80, 0x00),
+    (0x00, 0x00, 0x80),
+    (0x80, 0x00, 0x80),
+    (0x00, 0x80, 0x80),
+    (0xC0, 0xC0, 0xC0),
+    (0x80, 0x80, 0x80),
+    (0xFF, 0x00, 0x00),
+    (0x00, 0xFF, 0x00),
+    (0xFF, 0xFF, 0x00),
+    (0x00, 0x00, 0xFF),
+    (0xFF, 0x00, 0xFF),
+    (0x00, 0xFF, 0xFF),
+    (0xFF, 0xFF, 0xFF),
+];
+
+// Levels of each channel in the 6x6x6 color cube of the 256-color palette.
+pub(crate) const CUBE_LEVELS: [u8; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+
+/// A run of text that shares the same style.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StyledRun {
+    /// The text content.
+    pub text: String,
+    /// The foreground color, or [`None`] to use the default.
+    pub fg: Option<RunColor>,
+    /// The background color, or [`None`] to use the default.
+    pub bg: Option<RunColor>,
+    /// Whether the text is bold.
+    pub bold: bool,
+    /// Whether the text is italic.
+    pub italic: bool,
+    /// Whether the text is underlined.
+    pub underline: bool,
+}
+
+impl StyledRun {
+    /// Creates a [`StyledRun`] from a syntect [`Style`](syntect::highlighting::Style).
+    pub fn from_syntect<T>(style: syntect::highlighting::Style, text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            text: text.into(),
+            fg: RunColor::from_syntect(style.foreground),
+            bg: RunColor::from_syntect(style.background),
+            bold: style.font_style.contains(FontStyle::BOLD),
+            italic: style.font_style.contains(FontStyle::ITALIC),
+            underline: style.font_style.contains(FontStyle::UNDERLINE),
+        }
+    }
+}
+
+/// Highlights text from an iterator, returning the output as [`StyledLine`]s. Trailing newlines
+/// are not included in the output. Use [`Converter::styled_lines`] to quantize the colors to a
+/// [`ColorDepth`](crate::ColorDepth).
+pub fn styled_lines<'a, T>(
+    theme: &Theme,
+    source: T,
+    syntax: &SyntaxReference,
+    syntaxes: &SyntaxSet,
+) -> Result<Vec<StyledLine>, crate::Error>
+where
+    T: IntoIterator<Item = &'a str>,
+{
+    Converter::new().styled_lines(theme, source, syntax, syntaxes)
+}
+
+/// Renders the lines as an HTML `<pre>` block, using inline styles for each run. The block's
+/// background is taken from `background`, if provided, and runs that share it don't repeat it.
+pub fn to_html(lines: &[StyledLine], background: Option<RunColor>) -> String {
+    let mut html = match background {
+        Some(bg) => format!("<pre style=\"background-color:{};\">", css_color(bg)),
+        None => "<pre>".to_string(),
+    };
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            html.push('\n');
+        }
+        for run in line {
+            let mut style = String::new();
+            if let Some(fg) = run.fg {
+                let _ = write!(style, "color:{};", css_color(fg));
+            }
+            if let Some(bg) = run.bg.filter(|bg| Some(*bg) != background) {
+                let _ = write!(style, "background-color:{};", css_color(bg));
+            }
+            if run.bold {
+                style.push_str("font-weight:bold;");
+            }
+            if run.italic {
+                style.push_str("font-style:italic;");
+            }
+            if run.underline {
+                style.push_str("text-decoration:underline;");
+            }
+            let text = escape_html(&run.text);
+            if style.is_empty() {
+                html.push_str(&text);
+            } else {
+                let _ = write!(html, "<span style=\"{style}\">{text}</span>");
+            }
+        }
+    }
+    html.push_str("</pre>");
+    html
+}
+
+/// Renders the lines as a string containing ANSI escape sequences, suitable for printing directly
+/// to a terminal. Styles are reset at the end of each run.
+pub fn to_ansi_string(lines: &[StyledLine]) -> String {
+    let mut ansi = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            ansi.push('\n');
+        }
+        for run in line {
+            let mut codes = Vec::new();
+            if run.bold {
+                codes.push("1".to_string());
+            }
+            if run.italic {
+                codes.push("3".to_string());
+            }
+            if run.underline {
+                codes.push("4".to_string());
+            }
+            if let Some(fg) = run.fg {
+                codes.push(sgr_color(fg, 30));
+            }
+            if let Some(bg) = run.bg {
+                codes.push(sgr_color(bg, 40));
+            }
+            if codes.is_empty() {
+                ansi.push_str(&run.text);
+            } else {
+                let _ = write!(ansi, "\x1b[{}m{}\x1b[0m", codes.join(";"), run.text);
+            }
+        }
+    }
+    ansi
+}
+
+fn css_color(color: RunColor) -> String {
+    let (r, g, b) = color.to_rgb();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+// Returns the SGR parameters for the color. `base` is 30 for foreground colors and 40 for
+// background colors.
+fn sgr_color(color: RunColor, base: u8) -> String {
+    match color {
+        RunColor::Indexed(index @ 0..8) => (base + index).to_string(),
+        RunColor::Indexed(index @ 8..16) => (base + 60 + index - 8).to_string(),
+        RunColor::Indexed(index) => format!("{};5;{index}", base + 8),
+        RunColor::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", base + 8),
+    }
+}
+
+fn escape_html(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return text.into();
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.into()
+}
+
+// Removes a single trailing `\n` or `\r\n` from the line.
+pub(crate) fn strip_line_ending(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map_or(line, |line| line.strip_suffix('\r').unwrap_or(line))
+}
+
+// Replaces the line ending with `\n`, adding one if the line doesn't have one.
+pub(crate) fn with_lf(line: &str) -> Cow<'_, str> {
+    let content = strip_line_ending(line);
+    if content.len() + 1 == line.len() {
+        line.into()
+    } else {
+        (content.to_string() + "\n").into()
+    }
+}
+
+#[cfg(feature = "ratatui")]
+pub(crate) fn with_newline(line: &str) -> Cow<'_, str> {
+    if line.ends_with("\n") {
+        line.into()
+    } else {
+        (line.to_string() + "\n").into()
+    }
+}
+
+#[cfg(feature = "ratatui")]
+impl From<RunColor> for Color {
+    fn from(color: RunColor) -> Self {
+        match color {
+            RunColor::Indexed(index) => crate::convert::ansi_color_to_tui(index),
+            RunColor::Rgb(r, g, b) => Self::Rgb(r, g, b),
+        }
+    }
+}
+
+#[cfg(feature = "ratatui")]
+impl From<&StyledRun> for Style {
+    fn from(run: &StyledRun) -> Self {
+        let mut style = Self::new();
+        if let Some(fg) = run.fg {
+            style = style.fg(fg.into());
+        }
+        if let Some(bg) = run.bg {
+            style = style.bg(bg.into());
+        }
+        let mut modifier = Modifier::empty();
+        modifier.set(Modifier::BOLD, run.bold);
+        modifier.set(Modifier::ITALIC, run.italic);
+        modifier.set(Modifier::UNDERLINED, run.underline);
+        style.add_modifier(modifier)
+    }
+}
+
+#[cfg(feature = "ratatui")]
+impl From<StyledRun> for Span<'static> {
+    fn from(run: StyledRun) -> Self {
+        let style = Style::from(&run);
+        Span::styled(run.text, style)
+    }
+}
@@ -0,0 +1,382 @@
+use std::ops::Range;
+
+use ratatui_core::style::{Color, Style};
+use ratatui_core::text::{Line, Span};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Returns the display width of `text`. Characters with an ambiguous East Asian width are counted
+/// as two columns if `east_asian` is set, and one column otherwise.
+pub(crate) fn str_width(text: &str, east_asian: bool) -> usize {
+    if east_asian {
+        text.width_cjk()
+    } else {
+        text.width()
+    }
+}
+
+/// Returns the display width of `c`, following the same rules as [`str_width`].
+pub(crate) fn char_width(c: char, east_asian: bool) -> usize {
+    if east_asian {
+        c.width_cjk().unwrap_or(0)
+    } else {
+        c.width().unwrap_or(0)
+    }
+}
+
+/// Returns the display width of `text` once tabs are expanded to the next multiple of
+/// `tab_width`, following the same rules as [`str_width`] otherwise. Tabs are left at zero width
+/// if `tab_width` is `0`.
+pub(crate) fn expanded_width(text: &str, tab_width: usize, east_asian: bool) -> usize {
+    text.chars().fold(0, |column, c| {
+        if c == '\t' {
+            column + tab_width.saturating_sub(column.checked_rem(tab_width).unwrap_or(0))
+        } else {
+            column + char_width(c, east_asian)
+        }
+    })
+}
+
+/// Returns the combined display width of the spans, following the same rules as [`str_width`].
+pub(crate) fn spans_width(spans: &[Span<'_>], east_asian: bool) -> usize {
+    spans
+        .iter()
+        .map(|span| str_width(&span.content, east_asian))
+        .sum()
+}
+
+/// Merges adjacent spans that share the same style.
+pub(crate) fn coalesce<'a>(spans: Vec<Span<'a>>) -> Vec<Span<'a>> {
+    let mut coalesced: Vec<Span<'a>> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match coalesced.last_mut() {
+            Some(last) if last.style == span.style => last.content.to_mut().push_str(&span.content),
+            _ => coalesced.push(span),
+        }
+    }
+    coalesced
+}
+
+/// Limits the number of spans by merging everything past the limit into the last allowed span.
+pub(crate) fn cap<'a>(mut spans: Vec<Span<'a>>, max: usize) -> Vec<Span<'a>> {
+    let max = max.max(1);
+    if spans.len() <= max {
+        return spans;
+    }
+    let remainder: String = spans.drain(max..).map(|span| span.content).collect();
+    spans[max - 1].content.to_mut().push_str(&remainder);
+    spans
+}
+
+/// Replaces tabs with spaces up to the next tab stop. Columns are measured from the start of the
+/// first span, so stops line up relative to the code rather than the gutter.
+pub(crate) fn expand_tabs<'a>(
+    spans: Vec<Span<'a>>,
+    tab_width: usize,
+    east_asian: bool,
+) -> Vec<Span<'a>> {
+    let mut column = 0;
+    spans
+        .into_iter()
+        .map(|mut span| {
+            if !span.content.contains('\t') {
+                column += str_width(&span.content, east_asian);
+                return span;
+            }
+            let mut expanded = String::with_capacity(span.content.len());
+            for c in span.content.chars() {
+                if c == '\t' {
+                    let spaces =
+                        tab_width.saturating_sub(column.checked_rem(tab_width).unwrap_or(0));
+                    expanded.extend(std::iter::repeat_n(' ', spaces));
+                    column += spaces;
+                } else {
+                    expanded.push(c);
+                    column += char_width(c, east_asian);
+                }
+            }
+            span.content = expanded.into();
+            span
+        })
+        .collect()
+}
+
+/// Moves trailing spaces and tabs into a separate span with the given style, replacing the
+/// syntax style. Lines that only contain whitespace are left alone unless `blank` is set.
+pub(crate) fn split_trailing_whitespace<'a>(
+    mut spans: Vec<Span<'a>>,
+    style: Style,
+    blank: bool,
+) -> Vec<Span<'a>> {
+    let text: String = spans.iter().map(|span| span.content.as_ref()).collect();
+    let len = text.len();
+    let content_len = text.trim_end_matches([' ', '\t']).len();
+    if content_len == len || (content_len == 0 && !blank) {
+        return spans;
+    }
+
+    let mut whitespace = String::with_capacity(len - content_len);
+    let mut offset = 0;
+    for span in &mut spans {
+        let end = offset + span.content.len();
+        if end > content_len {
+            let split = content_len.saturating_sub(offset);
+            whitespace.push_str(&span.content[split..]);
+            span.content.to_mut().truncate(split);
+        }
+        offset = end;
+    }
+    spans.retain(|span| !span.content.is_empty());
+    spans.push(Span::styled(whitespace, style));
+    spans
+}
+
+/// Converts a line that borrows its text into one that owns it.
+pub(crate) fn into_owned(line: Line<'_>) -> Line<'static> {
+    Line {
+        spans: line
+            .spans
+            .into_iter()
+            .map(|span| Span::styled(span.content.into_owned(), span.style))
+            .collect(),
+        style: line.style,
+        alignment: line.alignment,
+    }
+}
+
+/// Patches styles over byte ranges of the concatenated span text, splitting spans where a range
+/// starts or ends partway through. Ranges must fall on character boundaries. Where ranges
+/// overlap, their styles are patched in order.
+pub(crate) fn patch_ranges<'a>(
+    spans: Vec<Span<'a>>,
+    ranges: &[(Range<usize>, Style)],
+) -> Vec<Span<'a>> {
+    if ranges.is_empty() {
+        return spans;
+    }
+    let style_at = |base: Style, pos: usize| {
+        ranges
+            .iter()
+            .filter(|(range, _)| range.contains(&pos))
+            .fold(base, |style, (_, patch)| style.patch(*patch))
+    };
+    let mut patched = Vec::with_capacity(spans.len() + ranges.len() * 2);
+    let mut offset = 0;
+    for span in spans {
+        let start = offset;
+        let end = offset + span.content.len();
+        offset = end;
+
+        let mut cuts: Vec<_> = ranges
+            .iter()
+            .flat_map(|(range, _)| [range.start, range.end])
+            .filter(|cut| *cut > start && *cut < end)
+            .collect();
+        if cuts.is_empty() {
+            // The span isn't split, so it keeps its content without copying.
+            let style = style_at(span.style, start);
+            patched.push(Span::styled(span.content, style));
+            continue;
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.push(end);
+
+        let mut pos = start;
+        for cut in cuts {
+            let style = style_at(span.style, pos);
+            patched.push(Span::styled(
+                span.content[pos - start..cut - start].to_string(),
+                style,
+            ));
+            pos = cut;
+        }
+    }
+    patched
+}
+
+/// Cuts off a line that doesn't fit within `width` columns, ending it with `marker` patched with
+/// `marker_style`. An empty marker cuts the line off without one.
+pub(crate) fn truncate_line(
+    line: &mut Line<'_>,
+    width: usize,
+    marker: &str,
+    marker_style: Style,
+    east_asian: bool,
+) {
+    if spans_width(&line.spans, east_asian) <= width {
+        return;
+    }
+    let spans = std::mem::take(&mut line.spans);
+    line.spans = truncate(spans, width, marker, east_asian);
+    if marker.is_empty() {
+        line.spans.pop();
+    } else if str_width(marker, east_asian) <= width
+        && let Some(marker) = line.spans.last_mut()
+    {
+        marker.style = marker.style.patch(marker_style);
+    }
+}
+
+/// Truncates spans so they fit within `width` columns, replacing the overflow with `ellipsis`.
+/// Text is only cut on grapheme boundaries, so combining characters are never split from their
+/// base character.
+pub(crate) fn truncate<'a>(
+    spans: Vec<Span<'a>>,
+    width: usize,
+    ellipsis: &str,
+    east_asian: bool,
+) -> Vec<Span<'a>> {
+    if spans_width(&spans, east_asian) <= width {
+        return spans;
+    }
+    let ellipsis_width = str_width(ellipsis, east_asian);
+    let available = width.saturating_sub(ellipsis_width);
+    let mut truncated = Vec::with_capacity(spans.len());
+    let mut used = 0;
+    let mut last_style = None;
+    for mut span in spans {
+        let mut end = 0;
+        let mut full = true;
+        for (i, grapheme) in span.content.grapheme_indices(true) {
+            let grapheme_width = str_width(grapheme, east_asian);
+            if used + grapheme_width > available {
+                full = false;
+                break;
+            }
+            used += grapheme_width;
+            end = i + grapheme.len();
+        }
+        last_style = Some(span.style);
+        if !full {
+            span.content.to_mut().truncate(end);
+            truncated.push(span);
+            break;
+        }
+        truncated.push(span);
+    }
+    if ellipsis_width <= width {
+        truncated.push(Span::styled(
+            ellipsis.to_string(),
+            last_style.unwrap_or_default(),
+        ));
+    }
+    truncated
+}
+
+/// Splits spans into rows that each fit within `width` columns, preserving the style of every
+/// piece. When `words` is set, rows are broken after the last whitespace that fits and leading
+/// whitespace is dropped from the following row. Words that are too long to fit on their own row
+/// are broken between characters.
+pub(crate) fn wrap<'a>(
+    spans: Vec<Span<'a>>,
+    width: usize,
+    words: bool,
+    east_asian: bool,
+) -> Vec<Vec<Span<'a>>> {
+    let width = width.max(1);
+    let graphemes: Vec<_> = spans
+        .iter()
+        .enumerate()
+        .flat_map(|(i, span)| span.content.graphemes(true).map(move |g| (i, g)))
+        .collect();
+    let is_space = |i: usize| graphemes[i].1.trim().is_empty();
+    let row_width = |range: Range<usize>| -> usize {
+        graphemes[range]
+            .iter()
+            .map(|(_, g)| str_width(g, east_asian))
+            .sum()
+    };
+
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+    let mut last_break = None;
+    let mut i = 0;
+    while i < graphemes.len() {
+        let grapheme_width = str_width(graphemes[i].1, east_asian);
+        if used + grapheme_width > width && i > start {
+            let end = match last_break {
+                Some(end) if words && end > start => end,
+                _ => i,
+            };
+            rows.push(start..end);
+            start = end;
+            if words {
+                while start < graphemes.len() && is_space(start) {
+                    start += 1;
+                }
+            }
+            i = i.max(start);
+            used = row_width(start..i);
+            last_break = None;
+            continue;
+        }
+        used += grapheme_width;
+        i += 1;
+        if is_space(i - 1) {
+            last_break = Some(i);
+        }
+    }
+    if start < graphemes.len() || rows.is_empty() {
+        rows.push(start..graphemes.len());
+    }
+
+    rows.into_iter()
+        .map(|range| {
+            let mut row: Vec<(usize, String)> = Vec::new();
+            for &(span_index, grapheme) in &graphemes[range] {
+                match row.last_mut() {
+                    Some((last, text)) if *last == span_index => text.push_str(grapheme),
+                    _ => row.push((span_index, grapheme.to_string())),
+                }
+            }
+            row.into_iter()
+                .map(|(span_index, text)| Span::styled(text, spans[span_index].style))
+                .collect()
+        })
+        .collect()
+}
+
+/// Condenses a line of code into `width` cells, where each cell covers `scale` columns and is
+/// drawn as a block in the most common foreground color of the non-whitespace text it covers.
+pub(crate) fn minimap_row(
+    spans: &[Span<'_>],
+    width: usize,
+    scale: usize,
+    east_asian: bool,
+) -> Line<'static> {
+    let mut counts: Vec<Vec<(Color, usize)>> = vec![Vec::new(); width];
+    let mut column = 0;
+    for span in spans {
+        for c in span.content.chars() {
+            let bucket = column / scale.max(1);
+            column += char_width(c, east_asian);
+            let (Some(fg), Some(counts)) = (span.style.fg, counts.get_mut(bucket)) else {
+                continue;
+            };
+            if c.is_whitespace() {
+                continue;
+            }
+            match counts.iter_mut().find(|(color, _)| *color == fg) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((fg, 1)),
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .map(|counts| {
+            // Prefer the earliest color when counts are tied.
+            let dominant = counts
+                .into_iter()
+                .rev()
+                .max_by_key(|(_, count)| *count)
+                .map(|(color, _)| color);
+            match dominant {
+                Some(color) => Span::styled("█", Style::new().fg(color)),
+                None => Span::raw(" "),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,11 @@
+use std::io::Cursor;
+
+use syntect::highlighting::{Theme, ThemeSet};
+
+/// Loads a [`Theme`] from the contents of a `.tmTheme` file. This allows shipping a theme as-is,
+/// without first dumping it to syntect's binary format.
+///
+/// Returns [`Error::Theme`](crate::Error::Theme) if the theme can't be parsed.
+pub fn load_theme_from_tm_theme(bytes: &[u8]) -> Result<Theme, crate::Error> {
+    ThemeSet::load_from_reader(&mut Cursor::new(bytes)).map_err(crate::Error::Theme)
+}
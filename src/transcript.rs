@@ -0,0 +1,51 @@
+use ratatui_core::text::Text;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::{HighlightedText, Highlighter, LineHighlighter};
+
+/// A growing transcript of highlighted input, such as the history of an interactive console.
+///
+/// Each appended fragment continues from the parse state left by the previous one, so constructs
+/// that span multiple inputs (such as an unterminated string or block comment) are highlighted
+/// correctly. Create one using [`Highlighter::transcript`].
+#[derive(Debug)]
+pub struct Transcript<'a> {
+    text: HighlightedText<'static>,
+    line_highlighter: LineHighlighter<'a>,
+}
+
+impl<'a> Transcript<'a> {
+    pub(crate) fn new(
+        highlighter: &'a Highlighter,
+        syntax: &SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+    ) -> Self {
+        let line_highlighter = highlighter.line_highlighter(syntax, syntaxes);
+        Self {
+            text: line_highlighter
+                .highlighter()
+                .highlighted_text(Text::default()),
+            line_highlighter,
+        }
+    }
+
+    /// Highlights `input` and appends it to the transcript. Input containing multiple lines is
+    /// split on newlines.
+    pub fn push_str_highlighted(&mut self, input: &str) -> Result<(), crate::Error> {
+        for line in input.split_inclusive('\n') {
+            let line = self.line_highlighter.push(line)?;
+            self.text.push_line(line);
+        }
+        Ok(())
+    }
+
+    /// Returns the highlighted content of the transcript.
+    pub fn text(&self) -> &HighlightedText<'static> {
+        &self.text
+    }
+
+    /// Consumes the [`Transcript`] and returns the highlighted content.
+    pub fn into_text(self) -> HighlightedText<'static> {
+        self.text
+    }
+}
@@ -0,0 +1 @@
+select 'caf' from b;
@@ -1,17 +1,27 @@
+use std::borrow::Cow;
 use std::cell::LazyCell;
 use std::fs::File;
-use std::sync::LazyLock;
+use std::str::FromStr;
+use std::sync::{LazyLock, mpsc};
+use std::thread;
 
 use ratatui::Terminal;
 use ratatui::backend::TestBackend;
-use ratatui::style::Color;
-use ratatui::text::Span;
-use ratatui::widgets::Widget;
-use syntect::highlighting::ThemeSet;
+use ratatui::layout::Alignment;
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Span, Text};
+use ratatui::widgets::{Block, BorderType, ListItem, Paragraph, Widget};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{
+    ScopeSelectors, StyleModifier, Theme, ThemeItem, ThemeSet, ThemeSettings,
+};
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
 use syntect_assets::assets::HighlightingAssets;
-use tui_syntax_highlight::Highlighter;
+use tui_syntax_highlight::{
+    ColorDepth, CompilerError, Converter, HighlightedText, Highlighter, LineCache, Link,
+    OverrideBackground, ScopedSpan, ScrollState, SearchMatch, SyntaxHint, WrapMode, gutter, render,
+};
 
 static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
 static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
@@ -70,16 +80,95 @@ fn highlighter_override_bg() {
     assert_snapshot!("highlighter_override_bg", draw(40, 2, highlight));
 }
 
+#[test]
+fn get_background_color() {
+    let theme = ASSETS.with(|a| a.get_theme("Nord").clone());
+    let highlighter = Highlighter::new(theme);
+    assert_eq!(
+        highlighter.get_background_color(),
+        Some(Color::Rgb(46, 52, 64))
+    );
+    let highlighter = highlighter.override_background(Color::Black);
+    assert_eq!(highlighter.get_background_color(), Some(Color::Black));
+}
+
+#[test]
+fn highlighter_override_bg_none() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .override_background(OverrideBackground::None);
+    assert_eq!(highlighter.get_background_color(), None);
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a,b,c from table;\nselect b,c,d from table2;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    for line in &highlight.lines {
+        assert_eq!(line.style.bg, None);
+        assert!(line.spans.iter().all(|span| span.style.bg.is_none()));
+    }
+
+    let backend = TestBackend::new(40, 2);
+    let mut terminal = Terminal::new(backend).unwrap();
+    terminal
+        .draw(|f| {
+            f.render_widget(Block::new().bg(Color::Blue), f.area());
+            f.render_widget(highlight, f.area());
+        })
+        .unwrap();
+    assert!(
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .all(|cell| cell.bg == Color::Blue)
+    );
+    assert_snapshot!("highlighter_override_bg_none", terminal.backend());
+}
+
+#[test]
+fn override_background_modes() {
+    let render_bg = |background: OverrideBackground| {
+        let highlight = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+            .override_background(Color::Black)
+            .override_background(background)
+            .highlight_lines(
+                LinesWithEndings::from("select 1;"),
+                SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+                &SYNTAXES,
+            )
+            .unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(12, 1)).unwrap();
+        terminal
+            .draw(|f| {
+                f.render_widget(Block::new().bg(Color::Blue), f.area());
+                f.render_widget(highlight, f.area());
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        // Check a cell under the code rather than the gutter.
+        buffer[(6, 0)].bg
+    };
+
+    assert_eq!(render_bg(OverrideBackground::Theme), Color::Rgb(43, 48, 59));
+    assert_eq!(render_bg(OverrideBackground::Color(Color::Red)), Color::Red);
+    assert_eq!(render_bg(Color::Reset.into()), Color::Reset);
+    assert_eq!(render_bg(OverrideBackground::None), Color::Blue);
+}
+
 #[test]
 fn highlighter_template() {
-    let highlighter =
-        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).gutter_template(|n, style| {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).gutter_template(
+        |line_number, style| {
             vec![
-                Span::raw(n.to_string()),
+                Span::raw(line_number.to_string()),
                 Span::raw(" "),
                 Span::styled(">", style),
             ]
-        });
+        },
+    );
     let highlight = highlighter
         .highlight_lines(
             LinesWithEndings::from("select a,b,c from table;\nselect b,c,d from table2;"),
@@ -90,6 +179,57 @@ fn highlighter_template() {
     assert_snapshot!("highlighter_template", draw(40, 2, highlight));
 }
 
+#[test]
+fn gutter_template_context() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .current_line(Some(1))
+        .highlight_range(2..3)
+        .gutter_template_with_context(|context| {
+            let marker = match (context.is_current, context.is_highlighted) {
+                (true, _) => "▶",
+                (false, true) => "*",
+                (false, false) => " ",
+            };
+            vec![Span::styled(
+                format!("{marker}{} ", context.line_number),
+                context.base_style,
+            )]
+        });
+    let text = highlighter
+        .highlight_lines(
+            ["select a;", "select b;", "select c;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let rows: Vec<_> = text.lines.iter().map(ToString::to_string).collect();
+    assert_eq!(rows, [" 1 select a;", "▶2 select b;", "*3 select c;"]);
+
+    let text = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .gutter_template(|n, style| vec![Span::styled(format!("{n}> "), style)])
+        .highlight_lines(
+            ["select a;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(text.lines[0].to_string(), "1> select a;");
+}
+
+#[test]
+fn highlighter_builtin_template() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .gutter_template(gutter::numbers_with_arrow());
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a,b,c from table;\nselect b,c,d from table2;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_snapshot!("highlighter_builtin_template", draw(40, 2, highlight));
+}
+
 #[test]
 fn highlight_file_ansi() {
     let theme = ASSETS.with(|a| a.get_theme("ansi").clone());
@@ -120,6 +260,2940 @@ fn highlight_range() {
     assert_snapshot!("highlight_range", draw(40, 2, highlight));
 }
 
+#[test]
+fn trailing_whitespace() {
+    let source = "select a from b;  \n\t\nselect 'c \t'\t \n";
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .show_trailing_whitespace(Style::new().bg(Color::Red));
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from(source),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_snapshot!("trailing_whitespace", draw(40, 3, highlight));
+
+    let highlight = highlighter
+        .show_whitespace_on_blank(true)
+        .highlight_lines(
+            LinesWithEndings::from(source),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let blank = highlight.lines[1].spans.last().unwrap();
+    assert_eq!(blank.content, "    ");
+    assert_eq!(blank.style.bg, Some(Color::Red));
+}
+
+#[test]
+fn line_highlighter_push() {
+    let source = "select a /* multi\nline */ from table;\nselect b from table2;";
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let batch = highlighter
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+
+    let mut line_highlighter = highlighter.line_highlighter(syntax, &SYNTAXES);
+    let mut pushed = Vec::new();
+    for line in LinesWithEndings::from(source) {
+        pushed.push(line_highlighter.push(line).unwrap());
+    }
+    assert_eq!(line_highlighter.line_count(), 3);
+    assert_eq!(pushed[2].spans[1].content, "3");
+    assert_eq!(Text::from(pushed), batch);
+}
+
+#[test]
+fn dim_low_contrast() {
+    let background = syntect::highlighting::Color {
+        r: 0x20,
+        g: 0x20,
+        b: 0x20,
+        a: 0xFF,
+    };
+    let theme = Theme {
+        settings: ThemeSettings {
+            foreground: Some(syntect::highlighting::Color::WHITE),
+            background: Some(background),
+            ..Default::default()
+        },
+        scopes: vec![ThemeItem {
+            scope: ScopeSelectors::from_str("keyword").unwrap(),
+            style: StyleModifier {
+                foreground: Some(syntect::highlighting::Color {
+                    r: 0x28,
+                    g: 0x28,
+                    b: 0x28,
+                    a: 0xFF,
+                }),
+                ..Default::default()
+            },
+        }],
+        ..Default::default()
+    };
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+
+    let highlight = Highlighter::new(theme.clone())
+        .dim_low_contrast(true)
+        .highlight_lines(["select a from b"], syntax, &SYNTAXES)
+        .unwrap();
+    let spans = &highlight.lines[0].spans;
+    let keyword = spans.iter().find(|s| s.content == "select").unwrap();
+    assert!(keyword.style.add_modifier.contains(Modifier::DIM));
+    assert_eq!(keyword.style.fg, None);
+    let identifier = spans.iter().find(|s| s.content.contains('a')).unwrap();
+    assert!(!identifier.style.add_modifier.contains(Modifier::DIM));
+
+    let highlight = Highlighter::new(theme)
+        .highlight_lines(["select a from b"], syntax, &SYNTAXES)
+        .unwrap();
+    let keyword = highlight.lines[0]
+        .spans
+        .iter()
+        .find(|s| s.content == "select")
+        .unwrap();
+    assert!(!keyword.style.add_modifier.contains(Modifier::DIM));
+}
+
+#[test]
+fn scope_background() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .override_background(Color::Black)
+        .scope_background("string", Color::Blue);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select 'text' from table;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let spans = &highlight.lines[0].spans;
+    let string = spans.iter().find(|s| s.content == "text").unwrap();
+    assert_eq!(string.style.bg, Some(Color::Blue));
+    let keyword = spans.iter().find(|s| s.content == "select").unwrap();
+    assert_eq!(keyword.style.bg, None);
+    assert_eq!(highlight.lines[0].style.bg, Some(Color::Black));
+}
+
+#[test]
+fn styled_lines_match_spans() {
+    let source = "select a,b,c from table;\nselect 'b' from table2; -- comment";
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let styled =
+        render::styled_lines(&theme, LinesWithEndings::from(source), syntax, &SYNTAXES).unwrap();
+    let highlight = Highlighter::new(theme)
+        .line_numbers(false)
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+
+    assert_eq!(styled.len(), highlight.lines.len());
+    for (runs, line) in styled.into_iter().zip(highlight.lines) {
+        let spans: Vec<_> = line
+            .spans
+            .into_iter()
+            .filter(|span| !span.content.is_empty())
+            .map(|span| Span::styled(span.content, line.style.patch(span.style)))
+            .collect();
+        let runs: Vec<Span> = runs.into_iter().map(Span::from).collect();
+        assert_eq!(runs, spans);
+    }
+}
+
+#[test]
+fn max_spans_per_line() {
+    let source = (0..200)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let source = format!("select {source} from table;");
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .max_spans_per_line(10);
+    let highlight = highlighter
+        .highlight_lines([source.as_str()], syntax, &SYNTAXES)
+        .unwrap();
+    let line = &highlight.lines[0];
+    assert_eq!(line.spans.len(), 10);
+    assert_eq!(line.to_string(), source);
+
+    let uncapped = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .highlight_lines([source.as_str()], syntax, &SYNTAXES)
+        .unwrap();
+    assert!(uncapped.lines[0].spans.len() > 10);
+
+    // Trailing whitespace is split into its own span before the limit is applied.
+    let source = format!("{source}   ");
+    let highlight = highlighter
+        .show_trailing_whitespace(Style::new().bg(Color::Red))
+        .highlight_lines([source.as_str()], syntax, &SYNTAXES)
+        .unwrap();
+    let line = &highlight.lines[0];
+    assert_eq!(line.spans.len(), 10);
+    assert_eq!(line.to_string(), source);
+}
+
+#[test]
+fn into_block_paragraph() {
+    let theme = ASSETS.with(|a| a.get_theme("Nord").clone());
+    let highlighter = Highlighter::new(theme);
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = "select a,b,c from table;\nselect b,c,d from table2;";
+    let block = Block::bordered().title("code");
+    let paragraph = highlighter
+        .into_block_paragraph(
+            LinesWithEndings::from(source),
+            syntax,
+            &SYNTAXES,
+            block.clone(),
+        )
+        .unwrap();
+
+    let text = highlighter
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+    let bg = highlighter.get_background_color().unwrap();
+    assert_eq!(paragraph, Paragraph::new(text).bg(bg).block(block));
+}
+
+#[test]
+fn combining_characters_align() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let highlight = highlighter
+        .highlight_lines(
+            ["-- e\u{301}x", "-- ex"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(20, 2, highlight);
+    let buffer = backend.buffer();
+    assert_eq!(buffer[(8, 0)].symbol(), "e\u{301}");
+    assert_eq!(buffer[(9, 0)].symbol(), "x");
+    assert_eq!(buffer[(9, 1)].symbol(), "x");
+}
+
+#[test]
+fn language_map() {
+    let path = "./tests/assets/Jenkinsfile";
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let groovy = highlighter
+        .highlight_reader(
+            File::open(path).unwrap(),
+            SYNTAXES.find_syntax_by_name("Groovy").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let unmapped = highlighter.highlight_file(path, &SYNTAXES).unwrap();
+    assert_ne!(unmapped, groovy);
+
+    let highlighter = highlighter.language_map(vec![(
+        tui_syntax_highlight::glob::Pattern::new("Jenkinsfile").unwrap(),
+        "Groovy".to_string(),
+    )]);
+    let mapped = highlighter.highlight_file(path, &SYNTAXES).unwrap();
+    assert_eq!(mapped, groovy);
+}
+
+#[test]
+fn lossy_utf8() {
+    let path = "./tests/assets/invalid_utf8.sql";
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let err = highlighter.highlight_file(path, &SYNTAXES).unwrap_err();
+    assert!(matches!(err, tui_syntax_highlight::Error::Read(_)));
+
+    let highlight = highlighter
+        .lossy_utf8(true)
+        .highlight_file(path, &SYNTAXES)
+        .unwrap();
+    assert_eq!(
+        highlight.lines[0].to_string(),
+        " 1 │ select 'caf\u{FFFD}' from b;"
+    );
+}
+
+#[test]
+fn highlight_file_errors() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let err = highlighter
+        .highlight_file("./tests/assets/does-not-exist.sql", &SYNTAXES)
+        .unwrap_err();
+    assert!(matches!(err, tui_syntax_highlight::Error::Read(_)));
+
+    let err = highlighter
+        .highlight_reader(
+            &b"select 1;\n\xff\n"[..],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap_err();
+    assert!(matches!(err, tui_syntax_highlight::Error::Read(_)));
+}
+
+#[test]
+fn highlight_str_auto() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let highlight_as = |source, name| {
+        highlighter
+            .highlight_lines(
+                LinesWithEndings::from(source),
+                SYNTAXES.find_syntax_by_name(name).unwrap(),
+                &SYNTAXES,
+            )
+            .unwrap()
+    };
+
+    let sql = "select a from b;\n";
+    assert_eq!(
+        highlighter
+            .highlight_str_auto(sql, "query.sql", &SYNTAXES)
+            .unwrap(),
+        highlight_as(sql, "SQL")
+    );
+
+    let script = "#!/bin/bash\necho \"hello\"\n";
+    assert_eq!(
+        highlighter
+            .highlight_str_auto(script, "scripts/run", &SYNTAXES)
+            .unwrap(),
+        highlight_as(script, "Bourne Again Shell (bash)")
+    );
+
+    assert_eq!(
+        highlighter
+            .highlight_str_auto(sql, "data.unknown", &SYNTAXES)
+            .unwrap(),
+        highlight_as(sql, "Plain Text")
+    );
+}
+
+#[test]
+fn highlight_reader_auto() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let highlight_as = |source, name| {
+        highlighter
+            .highlight_lines(
+                LinesWithEndings::from(source),
+                SYNTAXES.find_syntax_by_name(name).unwrap(),
+                &SYNTAXES,
+            )
+            .unwrap()
+    };
+
+    let script = "#!/bin/bash\necho \"hello\"\n";
+    assert_eq!(
+        highlighter
+            .highlight_reader_auto(script.as_bytes(), None, &SYNTAXES)
+            .unwrap(),
+        highlight_as(script, "Bourne Again Shell (bash)")
+    );
+
+    let sql = "select a from b;\n";
+    assert_eq!(
+        highlighter
+            .highlight_reader_auto(sql.as_bytes(), Some(SyntaxHint::Token("sql")), &SYNTAXES)
+            .unwrap(),
+        highlight_as(sql, "SQL")
+    );
+    // An unknown hint falls back to first line detection, then plain text.
+    assert_eq!(
+        highlighter
+            .highlight_reader_auto(
+                script.as_bytes(),
+                Some(SyntaxHint::Token("nope")),
+                &SYNTAXES
+            )
+            .unwrap(),
+        highlight_as(script, "Bourne Again Shell (bash)")
+    );
+    assert_eq!(
+        highlighter
+            .highlight_reader_auto(sql.as_bytes(), None, &SYNTAXES)
+            .unwrap(),
+        highlight_as(sql, "Plain Text")
+    );
+}
+
+#[test]
+fn fallback_syntax() {
+    let sql = "select a from b;\n";
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let expected = highlighter
+        .highlight_lines(
+            LinesWithEndings::from(sql),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+
+    let with_fallback = highlighter
+        .clone()
+        .fallback_syntax(SYNTAXES.find_syntax_by_name("SQL").cloned());
+    assert_eq!(
+        with_fallback
+            .highlight_str_auto(sql, "data.unknown", &SYNTAXES)
+            .unwrap(),
+        expected
+    );
+    // Detection still takes priority over the fallback.
+    assert_eq!(
+        with_fallback
+            .highlight_str_auto("fn main() {}\n", "main.rs", &SYNTAXES)
+            .unwrap(),
+        highlighter
+            .highlight_str_auto("fn main() {}\n", "main.rs", &SYNTAXES)
+            .unwrap()
+    );
+
+    let no_plain_text = syntect::parsing::SyntaxSetBuilder::new().build();
+    let err = highlighter
+        .highlight_str_auto(sql, "data.unknown", &no_plain_text)
+        .unwrap_err();
+    assert!(matches!(err, tui_syntax_highlight::Error::UnknownSyntax));
+
+    let plain_text = highlighter
+        .clone()
+        .fallback_syntax(Some(SYNTAXES.find_syntax_plain_text().clone()));
+    assert_eq!(
+        plain_text
+            .highlight_str_auto(sql, "data.unknown", &SYNTAXES)
+            .unwrap(),
+        highlighter
+            .highlight_lines(
+                LinesWithEndings::from(sql),
+                SYNTAXES.find_syntax_plain_text(),
+                &SYNTAXES,
+            )
+            .unwrap()
+    );
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn highlight_files_parallel() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let paths = [
+        std::path::PathBuf::from("./tests/assets/test_file.rs"),
+        std::path::PathBuf::from("./tests/assets/missing.rs"),
+        std::path::PathBuf::from("./tests/assets/Jenkinsfile"),
+    ];
+    let results = highlighter.highlight_files_parallel(&paths, &SYNTAXES);
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0].as_ref().unwrap(),
+        &highlighter.highlight_file(&paths[0], &SYNTAXES).unwrap()
+    );
+    assert!(matches!(
+        results[1],
+        Err(tui_syntax_highlight::Error::Read(_))
+    ));
+    assert_eq!(
+        results[2].as_ref().unwrap(),
+        &highlighter.highlight_file(&paths[2], &SYNTAXES).unwrap()
+    );
+}
+
+#[test]
+fn should_highlight_async() {
+    let highlighter =
+        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).async_threshold(1024);
+    assert!(!highlighter.should_highlight_async(1023));
+    assert!(highlighter.should_highlight_async(1024));
+    assert!(highlighter.should_highlight_async(4096));
+
+    assert_eq!(Highlighter::estimate_lines(""), 0);
+    assert_eq!(Highlighter::estimate_lines("a"), 1);
+    assert_eq!(Highlighter::estimate_lines("a\nb\n"), 2);
+    assert_eq!(Highlighter::estimate_lines("a\nb\nc"), 3);
+}
+
+#[test]
+fn with_background() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let text = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a,b,c from table;\nselect b,c,d from table2;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let highlight = HighlightedText::new(text.clone(), highlighter.get_background_color());
+    let recolored = highlight.with_background(Some(Color::Black));
+
+    assert_eq!(recolored.background(), Some(Color::Black));
+    for (line, original) in recolored.text().lines.iter().zip(&text.lines) {
+        // The gutter has its own background, so only the code is recolored.
+        for (span, original) in line.spans.iter().zip(&original.spans).skip(5) {
+            assert_eq!(line.style.patch(span.style).bg, Some(Color::Black));
+            assert_eq!(span.style.fg, original.style.fg);
+            assert_eq!(span.content, original.content);
+        }
+    }
+}
+
+#[test]
+fn indentation_folds() {
+    let source = "fn main() {\n    if x {\n        y();\n\n        z();\n    }\n}\n\nfn other() {}";
+    let folds = Highlighter::indentation_folds(source);
+    assert_eq!(folds, vec![1..6, 2..5]);
+}
+
+#[test]
+fn try_new() {
+    let err = Highlighter::try_new(Theme::default()).unwrap_err();
+    assert!(matches!(err, tui_syntax_highlight::Error::InvalidTheme(_)));
+    assert!(Highlighter::try_new(THEMES.themes["base16-ocean.dark"].clone()).is_ok());
+}
+
+#[test]
+fn current_line_separator() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_number_separator_style(Color::DarkGray)
+        .current_line_separator_style(Color::White)
+        .current_line(Some(1));
+    let highlight = highlighter
+        .highlight_lines(
+            ["select a from b;", "select c from d;", "select e from f;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let separators: Vec<_> = highlight
+        .lines
+        .iter()
+        .map(|line| line.spans[3].style.fg)
+        .collect();
+    assert_eq!(
+        separators,
+        [
+            Some(Color::DarkGray),
+            Some(Color::White),
+            Some(Color::DarkGray)
+        ]
+    );
+}
+
+#[test]
+fn tui_color_round_trip() {
+    let converter = Converter::new();
+    for color in [Color::Indexed(100), Color::Rgb(12, 34, 56), Color::Yellow] {
+        let syntect_color = converter.tui_color_to_syntect(color);
+        assert_eq!(converter.syntect_color_to_tui(syntect_color), Some(color));
+    }
+    assert_eq!(
+        converter.syntect_color_to_tui(converter.tui_color_to_syntect(Color::Reset)),
+        None
+    );
+}
+
+#[test]
+fn tui_style_round_trip() {
+    let converter = Converter::new();
+    let styles = [
+        Style::new()
+            .fg(Color::Rgb(12, 34, 56))
+            .bg(Color::Indexed(100))
+            .add_modifier(Modifier::BOLD | Modifier::ITALIC),
+        Style::new().fg(Color::LightCyan).underlined(),
+        Style::new().bg(Color::Black),
+        Style::new(),
+    ];
+    for style in styles {
+        let syntect_style = converter.tui_style_to_syntect(style);
+        assert_eq!(converter.syntect_style_to_tui(syntect_style), style);
+    }
+
+    // Modifiers without a syntect equivalent are dropped.
+    let syntect_style =
+        converter.tui_style_to_syntect(Style::new().fg(Color::Red).add_modifier(Modifier::DIM));
+    assert_eq!(
+        converter.syntect_style_to_tui(syntect_style),
+        Style::new().fg(Color::Red)
+    );
+}
+
+#[test]
+fn downsample_colors() {
+    let red = syntect::highlighting::Color {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 0xFF,
+    };
+    let convert = |depth| Converter::new().downsample(depth).syntect_color_to_tui(red);
+    assert_eq!(convert(ColorDepth::TrueColor), Some(Color::Rgb(255, 0, 0)));
+    assert_eq!(convert(ColorDepth::Ansi256), Some(Color::Indexed(196)));
+    // The basic colors use the xterm palette, where pure red is the bright variant.
+    assert_eq!(convert(ColorDepth::Ansi16), Some(Color::LightRed));
+    assert_eq!(convert(ColorDepth::Monochrome), None);
+    let dark_red = syntect::highlighting::Color { r: 128, ..red };
+    assert_eq!(
+        Converter::new()
+            .downsample(ColorDepth::Ansi16)
+            .syntect_color_to_tui(dark_red),
+        Some(Color::Red)
+    );
+
+    // Indexed colors outside of the basic 16 are quantized too, using the same palette that
+    // render::to_html uses for them.
+    let converter = Converter::new().downsample(ColorDepth::Ansi16);
+    let indexed = converter.tui_color_to_syntect(Color::Indexed(21));
+    assert_eq!(render::RunColor::Indexed(21).to_rgb(), (0, 0, 255));
+    assert_eq!(
+        converter.syntect_color_to_tui(indexed),
+        Some(Color::LightBlue)
+    );
+}
+
+#[test]
+fn highlighter_downsample() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .downsample(ColorDepth::Ansi16);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select * from users;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let colors: Vec<_> = highlight.lines[0]
+        .spans
+        .iter()
+        .flat_map(|span| [span.style.fg, span.style.bg])
+        .flatten()
+        .collect();
+    assert!(!colors.is_empty());
+    assert!(
+        colors
+            .iter()
+            .all(|color| !matches!(color, Color::Rgb(..) | Color::Indexed(..)))
+    );
+}
+
+#[test]
+fn syntect_style_to_tui_channel_order() {
+    let color = |r, g, b| syntect::highlighting::Color { r, g, b, a: 0xFF };
+    let style = syntect::highlighting::Style {
+        foreground: color(10, 20, 30),
+        background: color(40, 50, 60),
+        font_style: syntect::highlighting::FontStyle::empty(),
+    };
+    let tui_style = Converter::new().syntect_style_to_tui(style);
+    assert_eq!(tui_style.fg, Some(Color::Rgb(10, 20, 30)));
+    assert_eq!(tui_style.bg, Some(Color::Rgb(40, 50, 60)));
+}
+
+#[test]
+fn current_line_fills_empty_row() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .current_line_style(Style::new().bg(Color::Blue))
+        .current_line(Some(1));
+    let text = highlighter
+        .highlight_lines(
+            ["select a from b;", "", "select c from d;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let highlight = HighlightedText::new(text, highlighter.get_background_color());
+    let backend = draw(20, 3, highlight);
+    let buffer = backend.buffer();
+    for x in 0..20 {
+        assert_eq!(buffer[(x, 1)].bg, Color::Blue);
+        assert_ne!(buffer[(x, 0)].bg, Color::Blue);
+    }
+}
+
+#[test]
+fn render_highlighted_text_ref() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .current_line(Some(1))
+        .current_line_style(Style::new().bg(Color::Blue));
+    let text = highlighter
+        .highlight_lines(
+            ["select a from b;", "", "select c from d;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let highlight = HighlightedText::new(text, highlighter.get_background_color());
+
+    let backend = TestBackend::new(20, 3);
+    let mut terminal = Terminal::new(backend).unwrap();
+    let mut frames = Vec::new();
+    for _ in 0..2 {
+        terminal
+            .draw(|f| f.render_widget(&highlight, f.area()))
+            .unwrap();
+        frames.push(terminal.backend().buffer().clone());
+    }
+    assert_eq!(frames[0], frames[1]);
+    assert_eq!(&frames[0], draw(20, 3, highlight).buffer());
+}
+
+#[test]
+fn highlighted_text_from_text() {
+    let highlight = HighlightedText::from_text(Text::from("select 1;\nselect 22;"));
+    assert_eq!(highlight.background(), None);
+    // Text methods are available through Deref.
+    assert_eq!(highlight.lines.len(), 2);
+    assert_eq!(highlight.width(), 10);
+    assert_eq!(highlight.height(), 2);
+}
+
+#[test]
+fn scroll_highlighted_text() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let text = highlighter
+        .highlight_lines(
+            ["select a;", "select b;", "select c;", "select d;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let highlight = HighlightedText::new(text, highlighter.get_background_color());
+
+    let backend = draw(20, 3, highlight.clone().scroll((2, 0)));
+    let buffer = backend.buffer();
+    let row = |y: u16| (0..20).map(|x| buffer[(x, y)].symbol()).collect::<String>();
+    assert_eq!(row(0), " 3 │ select c;      ");
+    assert_eq!(row(1), " 4 │ select d;      ");
+    // The background fills the rows past the end of the content.
+    assert_eq!(row(2), " ".repeat(20));
+    assert_eq!(buffer[(19, 2)].bg, Color::Rgb(43, 48, 59));
+
+    let mut state = ScrollState::new();
+    let mut terminal = Terminal::new(TestBackend::new(20, 2)).unwrap();
+    let mut render = |state: &mut ScrollState| {
+        terminal
+            .draw(|f| f.render_stateful_widget(&highlight, f.area(), state))
+            .unwrap();
+        terminal.backend().buffer()[(5, 0)].symbol().to_string()
+            + terminal.backend().buffer()[(12, 0)].symbol()
+    };
+    assert_eq!(render(&mut state), "sa");
+    state.page_down();
+    assert_eq!(state.offset(), (2, 0));
+    assert_eq!(render(&mut state), "sc");
+    // Paging stops at the last page.
+    state.page_down();
+    assert_eq!(state.offset(), (2, 0));
+    state.set_offset((10, 0));
+    assert_eq!(render(&mut state), "sc");
+    assert_eq!(state.offset(), (2, 0));
+    state.page_up();
+    assert_eq!(render(&mut state), "sa");
+}
+
+#[test]
+fn scroll_state_before_render() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let highlight = highlighter
+        .highlight_lines_widget(
+            ["select a;", "select b;", "select c;", "select d;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+
+    let mut state = ScrollState::new();
+    state.scroll_down(1);
+    assert_eq!(state.offset(), (1, 0));
+    let mut terminal = Terminal::new(TestBackend::new(20, 2)).unwrap();
+    terminal
+        .draw(|f| f.render_stateful_widget(&highlight, f.area(), &mut state))
+        .unwrap();
+    assert_eq!(terminal.backend().buffer()[(12, 0)].symbol(), "b");
+
+    // Scrolling past the end is limited to the last page once the content is rendered.
+    let mut state = ScrollState::new();
+    state.scroll_down(10);
+    terminal
+        .draw(|f| f.render_stateful_widget(&highlight, f.area(), &mut state))
+        .unwrap();
+    assert_eq!(state.offset(), (2, 0));
+    assert_eq!(terminal.backend().buffer()[(12, 0)].symbol(), "c");
+}
+
+#[test]
+fn highlighted_text_dimensions() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let source = "select a,b,c from table;\nselect b,c,d from table2;";
+    let highlight = highlighter
+        .highlight_lines_widget(
+            LinesWithEndings::from(source),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(highlight.line_count(), 2);
+    // 5 columns for the gutter and 25 for the longest line.
+    assert_eq!(highlight.max_width(), 30);
+
+    let wrapped = highlighter
+        .wrap(WrapMode::Word, 20)
+        .highlight_lines_widget(
+            LinesWithEndings::from(source),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(wrapped.line_count(), 4);
+    assert!(wrapped.max_width() <= 20);
+}
+
+#[test]
+fn current_line_layering() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .current_line_style(Style::new().bg(Color::Blue).add_modifier(Modifier::BOLD))
+        .current_line(Some(1))
+        .highlight_range(1..3)
+        .highlight_style(Style::new().bg(Color::Yellow))
+        .search_highlight("from", Style::new().bg(Color::Red));
+    let text = highlighter
+        .highlight_lines(
+            ["select a from b;", "select c from d;", "select e from f;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let highlight = HighlightedText::new(text, highlighter.get_background_color());
+    let backend = draw(30, 3, highlight);
+    let buffer = backend.buffer();
+
+    // Theme background and syntax colors on a plain line, with a darker gutter.
+    assert_eq!(buffer[(0, 0)].bg, Color::Rgb(36, 40, 50));
+    assert_eq!(buffer[(5, 0)].bg, Color::Rgb(43, 48, 59));
+    // The highlight range takes precedence over the current line's background in the code, but
+    // the gutter keeps the current line's background. The current line's modifiers still apply
+    // everywhere.
+    for x in [0, 1, 5, 25] {
+        let bg = if x < 5 { Color::Blue } else { Color::Yellow };
+        assert_eq!(buffer[(x, 1)].bg, bg);
+        assert!(buffer[(x, 1)].modifier.contains(Modifier::BOLD));
+        assert!(!buffer[(x, 2)].modifier.contains(Modifier::BOLD));
+    }
+    // Search matches are drawn above both.
+    assert_eq!(buffer[(14, 1)].bg, Color::Red);
+    assert!(buffer[(14, 1)].modifier.contains(Modifier::BOLD));
+    assert_eq!(buffer[(14, 1)].fg, buffer[(14, 0)].fg);
+}
+
+#[test]
+fn map_spans() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let text = highlighter
+        .highlight_lines(
+            ["select 'hunter2' from users;", "select 'ok' from b;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let redacted =
+        HighlightedText::new(text, highlighter.get_background_color()).map_spans(|_, _, span| {
+            if span.content.contains("hunter") {
+                Span::styled("****", span.style)
+            } else {
+                span
+            }
+        });
+    let lines: Vec<_> = redacted
+        .text()
+        .lines
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        lines,
+        [" 1 │ select '****' from users;", " 2 │ select 'ok' from b;"]
+    );
+}
+
+#[test]
+fn try_scope_styles() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    // Scopes are limited to 8 atoms.
+    let result = highlighter
+        .clone()
+        .try_scope_background("a.b.c.d.e.f.g.h.i", Color::Blue);
+    assert!(matches!(
+        result,
+        Err(tui_syntax_highlight::Error::ScopeSelector(_))
+    ));
+    assert!(
+        highlighter
+            .clone()
+            .try_scope_background("string", Color::Blue)
+            .is_ok()
+    );
+
+    let result = highlighter
+        .clone()
+        .try_scope_override("a.b.c.d.e.f.g.h.i", Style::new().bold());
+    assert!(matches!(
+        result,
+        Err(tui_syntax_highlight::Error::ScopeSelector(_))
+    ));
+    assert!(
+        highlighter
+            .clone()
+            .try_scope_override("comment", Style::new().bold())
+            .is_ok()
+    );
+
+    let overlay = THEMES.themes["InspiredGitHub"].clone();
+    let result = highlighter
+        .clone()
+        .try_overlay_theme(overlay.clone(), vec!["keyword", "a.b.c.d.e.f.g.h.i"]);
+    assert!(matches!(
+        result,
+        Err(tui_syntax_highlight::Error::ScopeSelector(_))
+    ));
+    assert!(
+        highlighter
+            .try_overlay_theme(overlay, vec!["keyword", "string"])
+            .is_ok()
+    );
+}
+
+#[test]
+fn overlay_theme() {
+    let red = syntect::highlighting::Color {
+        r: 0xFF,
+        g: 0x00,
+        b: 0x00,
+        a: 0xFF,
+    };
+    let overlay = Theme {
+        scopes: ["keyword", "variable"]
+            .into_iter()
+            .map(|scope| ThemeItem {
+                scope: ScopeSelectors::from_str(scope).unwrap(),
+                style: StyleModifier {
+                    foreground: Some(red),
+                    ..Default::default()
+                },
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let source = ["select a from b"];
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let primary = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let expected = primary.highlight_lines(source, syntax, &SYNTAXES).unwrap();
+    let highlight = primary
+        .overlay_theme(overlay, vec!["keyword"])
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+
+    for (span, expected) in highlight.lines[0]
+        .spans
+        .iter()
+        .zip(&expected.lines[0].spans)
+    {
+        if ["select", "from"].contains(&span.content.as_ref()) {
+            assert_eq!(span.style.fg, Some(Color::Rgb(0xFF, 0x00, 0x00)));
+        } else {
+            assert_eq!(span.style, expected.style);
+        }
+    }
+}
+
+#[test]
+fn tab_stops_relative_to_code() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).tab_width(4);
+    let highlight = highlighter
+        .highlight_lines(
+            ["\tx", "ab\tx", "abcd\tx"],
+            SYNTAXES.find_syntax_by_name("Plain Text").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(20, 3, highlight);
+    let buffer = backend.buffer();
+    // The gutter takes up 5 cells, so the code starts at column 5.
+    assert_eq!(buffer[(9, 0)].symbol(), "x");
+    assert_eq!(buffer[(9, 1)].symbol(), "x");
+    assert_eq!(buffer[(13, 2)].symbol(), "x");
+}
+
+#[test]
+fn line_style_background() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .scope_background("keyword", Color::Blue);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select a from b;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let line = &highlight.lines[0];
+    assert_eq!(line.style.bg, highlighter.get_background_color());
+    // The first five spans make up the gutter.
+    for span in &line.spans[..5] {
+        assert_eq!(span.style.bg, highlighter.get_gutter_background());
+    }
+    for span in &line.spans[5..] {
+        if ["select", "from"].contains(&span.content.as_ref()) {
+            assert_eq!(span.style.bg, Some(Color::Blue));
+        } else {
+            assert_eq!(span.style.bg, None);
+        }
+    }
+}
+
+#[cfg(feature = "encoding_rs")]
+#[test]
+fn highlight_bytes() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    // "select 'café' from b;" encoded as Windows-1252
+    let bytes = b"select 'caf\xE9' from b;";
+    let highlight = highlighter
+        .highlight_bytes(
+            bytes,
+            tui_syntax_highlight::encoding_rs::WINDOWS_1252,
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(highlight.lines[0].to_string(), " 1 │ select 'café' from b;");
+}
+
+#[test]
+fn search_highlight() {
+    let source = [
+        "select a,b,c from table;",
+        "select 'Table' from TABLE2; -- tables",
+    ];
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .search_highlight("table", Style::new().bg(Color::Yellow));
+    let plain = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let patched = |text: &Text<'_>| -> Vec<(String, Option<Color>)> {
+        text.lines
+            .iter()
+            .flat_map(|line| &line.spans)
+            .filter(|span| span.style.bg == Some(Color::Yellow))
+            .map(|span| (span.content.to_string(), span.style.fg))
+            .collect()
+    };
+
+    let highlight = highlighter
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let table_fg = plain.lines[0]
+        .spans
+        .iter()
+        .find(|span| span.content.contains("table"))
+        .unwrap()
+        .style
+        .fg;
+    let comment_fg = plain.lines[1].spans.last().unwrap().style.fg;
+    assert_eq!(
+        patched(&highlight),
+        [
+            ("table".to_string(), table_fg),
+            ("table".to_string(), comment_fg)
+        ]
+    );
+    assert_eq!(
+        highlighter.find_matches(source),
+        [
+            SearchMatch {
+                line: 0,
+                range: 18..23
+            },
+            SearchMatch {
+                line: 1,
+                range: 31..36
+            },
+        ]
+    );
+
+    let highlighter = highlighter.search_case_insensitive(true);
+    let highlight = highlighter
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let matches: Vec<_> = patched(&highlight)
+        .into_iter()
+        .map(|(text, _)| text)
+        .collect();
+    assert_eq!(matches, ["table", "Table", "TABLE", "table"]);
+    assert_eq!(highlighter.find_matches(source).len(), 4);
+
+    // A match spanning multiple tokens keeps each token's color.
+    let highlight = highlighter
+        .search_highlight("from tab", Style::new().bg(Color::Yellow))
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let keyword_fg = plain.lines[0].spans[2].style.fg;
+    assert_eq!(plain.lines[0].spans[2].content, "from");
+    assert_eq!(
+        patched(&highlight)[..2],
+        [
+            ("from".to_string(), keyword_fg),
+            (" tab".to_string(), table_fg)
+        ]
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn search_highlight_regex() {
+    let source = ["select a from b; -- select", "  select 'ünïcode' from c;"];
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let regex = |pattern| tui_syntax_highlight::regex::Regex::new(pattern).unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .search_highlight_regex(regex("^select"), Style::new().bg(Color::Yellow));
+    let patched = |highlighter: &Highlighter| -> Vec<String> {
+        highlighter
+            .highlight_lines(source, syntax, &SYNTAXES)
+            .unwrap()
+            .lines
+            .iter()
+            .flat_map(|line| &line.spans)
+            .filter(|span| span.style.bg == Some(Color::Yellow))
+            .map(|span| span.content.to_string())
+            .collect()
+    };
+
+    assert_eq!(patched(&highlighter), ["select"]);
+    assert_eq!(
+        highlighter.find_matches(source),
+        [SearchMatch {
+            line: 0,
+            range: 0..6
+        }]
+    );
+
+    // The `x*` branch matches an empty string at every position, which should be skipped.
+    let highlighter = highlighter.search_highlight_regex(
+        regex(r"\w*[^\x00-\x7F]\w*|x*"),
+        Style::new().bg(Color::Yellow),
+    );
+    assert_eq!(patched(&highlighter), ["ünïcode"]);
+    assert_eq!(
+        highlighter.find_matches(source),
+        [SearchMatch {
+            line: 1,
+            range: 10..19
+        }]
+    );
+
+    // The line ending is stripped before searching, including a carriage return.
+    let crlf = ["select a;\r\n", "select b;\n"];
+    let highlighter = highlighter.search_highlight_regex(regex(";$"), Style::new());
+    assert_eq!(
+        highlighter.find_matches(crlf),
+        [
+            SearchMatch {
+                line: 0,
+                range: 8..9
+            },
+            SearchMatch {
+                line: 1,
+                range: 8..9
+            }
+        ]
+    );
+}
+
+#[test]
+fn current_match() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .search_highlight("b", Style::new().bg(Color::Yellow))
+        .current_match(1, Style::new().bg(Color::Red));
+    let highlight = highlighter
+        .highlight_lines(
+            ["select b from t;", "select b, b from t;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let matches: Vec<_> = highlight
+        .lines
+        .iter()
+        .flat_map(|line| &line.spans)
+        .filter(|span| span.content == "b")
+        .map(|span| span.style.bg)
+        .collect();
+    assert_eq!(
+        matches,
+        [Some(Color::Yellow), Some(Color::Red), Some(Color::Yellow)]
+    );
+}
+
+#[test]
+fn transcript() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let mut transcript = highlighter.transcript(syntax, &SYNTAXES);
+    transcript.push_str_highlighted("select 'multi\n").unwrap();
+    transcript.push_str_highlighted("line' from b;").unwrap();
+
+    let expected = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select 'multi\nline' from b;"),
+            syntax,
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(transcript.text().text(), &expected);
+}
+
+#[test]
+fn fold_summary() {
+    let source: Vec<_> = (1..=7).map(|i| format!("select {i};")).collect();
+    let highlighter =
+        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).fold(2..5, "… 3 lines …");
+    let highlight = highlighter
+        .highlight_lines(
+            source.iter().map(String::as_str),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(20, 5, highlight);
+    assert_snapshot!("fold_summary", backend);
+}
+
+#[test]
+fn nested_folds_show_outer_summary() {
+    let source: Vec<_> = (1..=7).map(|i| format!("select {i};")).collect();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .fold(2..4, "inner")
+        .fold(1..6, "outer");
+    let highlight = highlighter
+        .highlight_lines(
+            source.iter().map(String::as_str),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let lines: Vec<_> = highlight.lines.iter().map(ToString::to_string).collect();
+    assert_eq!(lines, [" 1 │ select 1;", "     outer", " 7 │ select 7;"]);
+}
+
+#[test]
+fn fold_keeps_source_line_numbers() {
+    let source: Vec<_> = (1..=7).map(|i| format!("select {i};")).collect();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).fold(2..5, "");
+    let highlight = highlighter
+        .highlight_lines(
+            source.iter().map(String::as_str),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let lines: Vec<_> = highlight.lines.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        lines,
+        [
+            " 1 │ select 1;",
+            " 2 │ select 2;",
+            " 6 │ select 6;",
+            " 7 │ select 7;"
+        ]
+    );
+}
+
+#[test]
+fn into_list_items() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let text = highlighter
+        .highlight_lines(
+            ["select a from b;", "select c from d;", "select e from f;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let items =
+        HighlightedText::new(text.clone(), highlighter.get_background_color()).into_list_items();
+    assert_eq!(items.len(), 3);
+    for (item, line) in items.into_iter().zip(text.lines) {
+        assert_eq!(item, ListItem::new(line));
+    }
+}
+
+#[test]
+fn theme_is_dark() {
+    assert!(tui_syntax_highlight::theme_is_dark(
+        &THEMES.themes["base16-ocean.dark"]
+    ));
+    assert!(!tui_syntax_highlight::theme_is_dark(
+        &THEMES.themes["base16-ocean.light"]
+    ));
+    assert!(!tui_syntax_highlight::theme_is_dark(
+        &THEMES.themes["InspiredGitHub"]
+    ));
+}
+
+#[test]
+fn highlight_reader_streaming() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = "select a,b,c from table;\nselect b,c,d from table2;\nselect 1;";
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        s.spawn(|| {
+            highlighter.highlight_reader_streaming(source.as_bytes(), syntax, &SYNTAXES, tx);
+        });
+    });
+    let streamed: Vec<_> = rx.into_iter().map(Result::unwrap).collect();
+
+    let expected = highlighter
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(Text::from(streamed), expected);
+}
+
+#[test]
+fn highlight_iter() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = "select a from b;\n/* select c\nfrom d; */\nselect e from f;\n";
+    let expected = highlighter
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+    let lines: Vec<_> = highlighter
+        .highlight_iter(source.as_bytes(), syntax, &SYNTAXES)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(Text::from(lines), expected);
+
+    let first: Vec<_> = highlighter
+        .highlight_iter(source.as_bytes(), syntax, &SYNTAXES)
+        .take(2)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(first, expected.lines[..2]);
+}
+
+#[test]
+fn empty_input() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let empty = Text::default();
+    assert_eq!(
+        highlighter
+            .highlight_str_auto("", "query.sql", &SYNTAXES)
+            .unwrap(),
+        empty
+    );
+    assert_eq!(
+        highlighter
+            .highlight_str_auto("", "unknown", &SYNTAXES)
+            .unwrap(),
+        empty
+    );
+    assert_eq!(
+        highlighter
+            .highlight_lines("".lines(), syntax, &SYNTAXES)
+            .unwrap(),
+        empty
+    );
+    assert_eq!(
+        highlighter
+            .highlight_lines(Vec::<&str>::new(), syntax, &SYNTAXES)
+            .unwrap(),
+        empty
+    );
+    assert_eq!(
+        highlighter
+            .highlight_lines_borrowed(&[], syntax, &SYNTAXES)
+            .unwrap(),
+        empty
+    );
+    assert_eq!(
+        highlighter
+            .highlight_reader("".as_bytes(), syntax, &SYNTAXES)
+            .unwrap(),
+        empty
+    );
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn highlight_async_reader() {
+    use futures_util::StreamExt;
+
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = "select a from b;\n/* select c\nfrom d; */\nselect e from f;\n";
+    let reader = tokio::io::BufReader::new(source.as_bytes());
+    let lines: Vec<_> = highlighter
+        .highlight_async_reader(reader, syntax, &SYNTAXES)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let expected = highlighter
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(Text::from(lines), expected);
+
+    let source = b"select '\xff';\n";
+    let read = |highlighter: Highlighter| async move {
+        let reader = tokio::io::BufReader::new(&source[..]);
+        highlighter
+            .highlight_async_reader(reader, syntax, &SYNTAXES)
+            .collect::<Vec<_>>()
+            .await
+    };
+    let lines = read(highlighter.clone()).await;
+    assert!(matches!(
+        lines[..],
+        [Err(tui_syntax_highlight::Error::Read(_))]
+    ));
+    let lines = read(highlighter.lossy_utf8(true)).await;
+    assert_eq!(
+        lines[0].as_ref().unwrap().to_string(),
+        " 1 │ select '\u{FFFD}';"
+    );
+}
+
+#[test]
+fn resolve_syntax() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let name = |hint| {
+        highlighter
+            .resolve_syntax(hint, &SYNTAXES)
+            .map(|syntax| syntax.name.as_str())
+    };
+    assert_eq!(name(SyntaxHint::Name("Rust")), Some("Rust"));
+    assert_eq!(name(SyntaxHint::Name("rust")), None);
+    assert_eq!(name(SyntaxHint::Extension("sql")), Some("SQL"));
+    assert_eq!(name(SyntaxHint::Extension("Makefile")), Some("Makefile"));
+    assert_eq!(name(SyntaxHint::Extension("nope")), None);
+    assert_eq!(
+        name(SyntaxHint::FirstLine("#!/usr/bin/env python3")),
+        Some("Python")
+    );
+    assert_eq!(name(SyntaxHint::FirstLine("select 1;")), None);
+    assert_eq!(name(SyntaxHint::Token("rust")), Some("Rust"));
+    assert_eq!(name(SyntaxHint::Token("py")), Some("Python"));
+    assert_eq!(name(SyntaxHint::Token("nope")), None);
+}
+
+#[test]
+fn line_number_align() {
+    let source: Vec<_> = (1..=12).map(|i| format!("select {i};")).collect();
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let gutters = |highlighter: Highlighter| {
+        let text = highlighter
+            .highlight_lines(source.iter().map(String::as_str), syntax, &SYNTAXES)
+            .unwrap();
+        [8, 9, 10]
+            .map(|i| text.lines[i].to_string())
+            .map(|line| line.split("select").next().unwrap().to_string())
+    };
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+
+    assert_eq!(gutters(highlighter.clone()), [" 9 │ ", "10 │ ", "11 │ "]);
+    assert_eq!(
+        gutters(highlighter.clone().line_number_align(Alignment::Left)),
+        ["9  │ ", "10 │ ", "11 │ "]
+    );
+    assert_eq!(
+        gutters(
+            highlighter
+                .clone()
+                .line_number_padding(6)
+                .line_number_align(Alignment::Center)
+        ),
+        [" 9   │ ", " 10  │ ", " 11  │ "]
+    );
+    assert_eq!(
+        gutters(highlighter.line_number_separator("")),
+        [" 9 ", "10 ", "11 "]
+    );
+}
+
+#[test]
+fn auto_line_number_width() {
+    let highlighter =
+        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).auto_line_number_width(true);
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let gutters = |count: usize| {
+        let source: Vec<_> = (0..count).map(|_| "select 1;").collect();
+        let text = highlighter
+            .highlight_lines(source.iter().copied(), syntax, &SYNTAXES)
+            .unwrap();
+        [0, count - 1].map(|i| {
+            let line = text.lines[i].to_string();
+            line.split("select").next().unwrap().to_string()
+        })
+    };
+    assert_eq!(gutters(9), ["1 │ ", "9 │ "]);
+    assert_eq!(gutters(10), [" 1 │ ", "10 │ "]);
+    assert_eq!(gutters(99), [" 1 │ ", "99 │ "]);
+    assert_eq!(gutters(100), ["  1 │ ", "100 │ "]);
+
+    // The length of a reader isn't known, so the fixed padding is used.
+    let text = highlighter
+        .highlight_reader("select 1;\n".repeat(100).as_bytes(), syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(text.lines[0].to_string(), " 1 │ select 1;");
+    assert_eq!(text.lines[99].to_string(), "100 │ select 1;");
+}
+
+#[test]
+fn match_brackets() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let lines = [
+        "select count(*) from (select (a + b) as c",
+        "from t where d = '(' -- (",
+        ") as x;",
+    ];
+    let style = Style::new().bg(Color::Red);
+    let matched = |cursor| {
+        let text = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+            .line_numbers(false)
+            .match_brackets(cursor, style)
+            .highlight_lines(lines, syntax, &SYNTAXES)
+            .unwrap();
+        let mut positions = Vec::new();
+        for (line_number, line) in text.lines.iter().enumerate() {
+            let mut column = 0;
+            for span in &line.spans {
+                if span.style.bg == Some(Color::Red) {
+                    positions.push((line_number, column, span.content.to_string()));
+                }
+                column += span.content.chars().count();
+            }
+        }
+        positions
+    };
+
+    assert_eq!(
+        matched((0, 21)),
+        [(0, 21, "(".to_string()), (2, 0, ")".to_string())]
+    );
+    assert_eq!(
+        matched((0, 35)),
+        [(0, 29, "(".to_string()), (0, 35, ")".to_string())]
+    );
+    assert_eq!(
+        matched((0, 12)),
+        [(0, 12, "(".to_string()), (0, 14, ")".to_string())]
+    );
+    // Brackets in strings and comments are ignored.
+    assert_eq!(matched((1, 18)), []);
+    assert_eq!(matched((1, 24)), []);
+    // Not a bracket.
+    assert_eq!(matched((0, 0)), []);
+}
+
+#[test]
+fn highlight_diff() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let diff = "\
+diff --git a/query.sql b/query.sql
+--- a/query.sql
++++ b/query.sql
+@@ -10,3 +10,3 @@ comment
+ select a
+-from b;
++from c;
+ -- done
+";
+    let text = highlighter.highlight_diff(diff, syntax, &SYNTAXES).unwrap();
+    let lines: Vec<_> = text.lines.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        lines,
+        [
+            "diff --git a/query.sql b/query.sql",
+            "--- a/query.sql",
+            "+++ b/query.sql",
+            "@@ -10,3 +10,3 @@ comment",
+            "10 │  select a",
+            "11 │ -from b;",
+            "11 │ +from c;",
+            "12 │  -- done",
+        ]
+    );
+    assert_eq!(text.lines[3].style.fg, Some(Color::Cyan));
+    assert_eq!(text.lines[4].style.bg, Some(Color::Rgb(43, 48, 59)));
+    assert_eq!(text.lines[5].style.bg, Some(Color::Indexed(52)));
+    assert_eq!(text.lines[6].style.bg, Some(Color::Indexed(22)));
+
+    // Code is highlighted the same way as it would be outside of the diff.
+    let expected = highlighter
+        .highlight_lines(["from c;"], syntax, &SYNTAXES)
+        .unwrap();
+    let code: Vec<_> = text.lines[6].spans[6..].to_vec();
+    assert_eq!(code, expected.lines[0].spans[5..]);
+}
+
+#[test]
+fn scope_override() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .scope_override("comment", Style::new().fg(Color::Red).bold())
+        .scope_override("comment punctuation", Style::new().fg(Color::Blue));
+    let text = highlighter
+        .highlight_lines(["select a; -- note"], syntax, &SYNTAXES)
+        .unwrap();
+    let style_of = |content: &str| {
+        text.lines[0]
+            .spans
+            .iter()
+            .find(|span| span.content == content)
+            .unwrap()
+            .style
+    };
+
+    assert_eq!(style_of(" note").fg, Some(Color::Red));
+    assert!(style_of(" note").add_modifier.contains(Modifier::BOLD));
+    // The more specific selector wins for the comment marker, but the bold modifier from the
+    // broader selector still applies.
+    assert_eq!(style_of("--").fg, Some(Color::Blue));
+    assert!(style_of("--").add_modifier.contains(Modifier::BOLD));
+    assert_ne!(style_of("select").fg, Some(Color::Red));
+    assert!(!style_of("select").add_modifier.contains(Modifier::BOLD));
+}
+
+#[test]
+fn highlight_lines_scoped() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = ["select a -- note", "from b;"];
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+
+    let lines = highlighter
+        .clone()
+        .highlight_lines_scoped(source, syntax, &SYNTAXES)
+        .unwrap();
+    assert!(lines.iter().all(|(_, scopes)| scopes.is_empty()));
+
+    let lines = highlighter
+        .with_scopes(true)
+        .highlight_lines_scoped(source, syntax, &SYNTAXES)
+        .unwrap();
+    let expected = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let highlighted: Vec<_> = lines.iter().map(|(line, _)| line.clone()).collect();
+    assert_eq!(highlighted, expected.lines);
+
+    let (_, scopes) = &lines[0];
+    assert_eq!(
+        scopes[0],
+        ScopedSpan {
+            range: 0..6,
+            scope: "source.sql keyword.other.DML.sql".to_string()
+        }
+    );
+    let comment = scopes.last().unwrap();
+    assert_eq!(&source[0][comment.range.clone()], " note");
+    assert!(comment.scope.starts_with("source.sql comment.line"));
+    assert_eq!(lines[1].1.last().unwrap().range.end, source[1].len());
+}
+
+#[test]
+fn default_colors() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(Theme::default())
+        .default_foreground(Color::Black)
+        .default_background(Color::White);
+    assert_eq!(highlighter.get_background_color(), Some(Color::White));
+    assert_eq!(highlighter.get_line_number_style().fg, Some(Color::Black));
+
+    let text = highlighter
+        .highlight_lines(["select a from b;"], syntax, &SYNTAXES)
+        .unwrap();
+    let line = &text.lines[0];
+    assert_eq!(line.style.bg, Some(Color::White));
+    assert!(
+        line.spans
+            .iter()
+            .all(|span| span.style.fg == Some(Color::Black) && span.style.bg.is_none())
+    );
+
+    // Settings from the theme take precedence.
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let highlighter = Highlighter::new(theme.clone())
+        .default_foreground(Color::Black)
+        .default_background(Color::White);
+    assert_eq!(
+        highlighter.get_background_color(),
+        Highlighter::new(theme).get_background_color()
+    );
+}
+
+#[test]
+fn highlight_lines_width() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let source = ["select '世界' from b;", "select 1;"];
+    let text = highlighter
+        .highlight_lines_width(source, 16, syntax, &SYNTAXES)
+        .unwrap();
+    // The wide characters take two columns each, so only one fits before the marker.
+    assert_eq!(text.lines[0].to_string(), " 1 │ select '世…");
+    assert_eq!(text.lines[0].width(), 16);
+    assert_eq!(text.lines[1].to_string(), " 2 │ select 1;");
+    let marker = text.lines[0].spans.last().unwrap();
+    assert_eq!(marker.style.fg, highlighter.get_line_number_style().fg);
+
+    let text = highlighter
+        .truncate_marker(None)
+        .highlight_lines_width(source, 16, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(text.lines[0].to_string(), " 1 │ select '世");
+}
+
+#[test]
+fn zebra_stripe() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .zebra_stripe(
+            Style::new().bg(Color::Indexed(236)),
+            Style::new().bg(Color::Indexed(238)),
+        )
+        .highlight_range(2..3);
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a;\nselect b;\nselect c;\nselect d;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let widget = HighlightedText::new(highlight, highlighter.get_background_color());
+    let backend = draw(20, 4, widget);
+    let buffer = backend.buffer();
+    // The stripe covers the gutter and the padding after the code.
+    assert_eq!(buffer[(0, 0)].bg, Color::Indexed(238));
+    assert_eq!(buffer[(19, 0)].bg, Color::Indexed(238));
+    assert_eq!(buffer[(0, 1)].bg, Color::Indexed(236));
+    // Highlighted ranges are drawn over the stripe.
+    assert_eq!(buffer[(6, 2)].bg, Color::Yellow);
+    assert_eq!(buffer[(6, 3)].bg, Color::Indexed(236));
+    assert_snapshot!("zebra_stripe", backend);
+}
+
+#[cfg(feature = "plist-load")]
+#[test]
+fn tm_theme() {
+    let tm_theme = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>Inline</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>background</key>
+                <string>#101010</string>
+                <key>foreground</key>
+                <string>#E0E0E0</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>keyword</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#FF0000</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>"#;
+
+    let theme = tui_syntax_highlight::load_theme_from_tm_theme(tm_theme.as_bytes()).unwrap();
+    assert_eq!(theme.name.as_deref(), Some("Inline"));
+
+    let highlighter = Highlighter::from_tm_theme_reader(tm_theme.as_bytes()).unwrap();
+    assert_eq!(
+        highlighter.get_background_color(),
+        Some(Color::Rgb(16, 16, 16))
+    );
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(highlight.lines[0].spans[5].content, "select");
+    assert_eq!(
+        highlight.lines[0].spans[5].style.fg,
+        Some(Color::Rgb(255, 0, 0))
+    );
+
+    let err = Highlighter::from_tm_theme_reader(&b"not a theme"[..]).unwrap_err();
+    assert!(matches!(err, tui_syntax_highlight::Error::Theme(_)));
+}
+
+#[test]
+fn theme_accessors() {
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let highlighter = Highlighter::new(theme.clone());
+    assert_eq!(highlighter.theme(), &theme);
+    assert_eq!(highlighter.theme_name(), Some("Base16 Ocean Dark"));
+
+    let named = Highlighter::new_named("ocean", theme.clone());
+    assert_eq!(named.theme_name(), Some("ocean"));
+    assert_eq!(named.theme().settings.caret, theme.settings.caret);
+
+    let unnamed = Highlighter::new(Theme::default());
+    assert_eq!(unnamed.theme_name(), None);
+}
+
+#[test]
+fn selection() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .selection((2, 6), (0, 7))
+        .selection_style(Style::new().bg(Color::Blue));
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a;\nselect b;\nselect c;\nselect d;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(20, 4, highlight);
+    let buffer = backend.buffer();
+    let selected = |y: u16| {
+        (0..20)
+            .filter(|x| buffer[(*x, y)].bg == Color::Blue)
+            .collect::<Vec<_>>()
+    };
+    // The first line is selected from the start column to the end of the line.
+    assert_eq!(selected(0), (12..14).collect::<Vec<_>>());
+    // Interior lines are selected in full, without the gutter.
+    assert_eq!(selected(1), (5..14).collect::<Vec<_>>());
+    // The last line is selected up to the end column.
+    assert_eq!(selected(2), (5..11).collect::<Vec<_>>());
+    assert!(selected(3).is_empty());
+    assert_snapshot!("selection", backend);
+
+    let theme_selection = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    assert_eq!(
+        theme_selection.get_selection_style(),
+        Style::new().bg(Color::Rgb(79, 91, 102))
+    );
+}
+
+#[test]
+fn gutter_width_bounds() {
+    let source = "x\n".repeat(123);
+    let gutter = |highlighter: Highlighter, line: usize| {
+        let text = highlighter
+            .highlight_lines(
+                LinesWithEndings::from(source.as_str()),
+                SYNTAXES.find_syntax_plain_text(),
+                &SYNTAXES,
+            )
+            .unwrap();
+        let line: String = text.lines[line]
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        line.trim_end_matches('x').to_string()
+    };
+    let highlighter = || Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+
+    assert_eq!(gutter(highlighter(), 122), "123 │ ");
+    // Bounds that contain the natural width leave the gutter alone.
+    assert_eq!(
+        gutter(highlighter().gutter_width_bounds(6, 6), 122),
+        "123 │ "
+    );
+    assert_eq!(
+        gutter(highlighter().gutter_width_bounds(8, 10), 0),
+        "    1 │ "
+    );
+    // Narrow gutters keep the last digits behind the marker.
+    assert_eq!(
+        gutter(highlighter().gutter_width_bounds(0, 5), 122),
+        "…3 │ "
+    );
+    assert_eq!(gutter(highlighter().gutter_width_bounds(0, 5), 8), " 9 │ ");
+    assert_eq!(
+        gutter(
+            highlighter()
+                .gutter_width_bounds(0, 6)
+                .gutter_overflow_marker('<')
+                .line_number_padding(6),
+            122
+        ),
+        "123 │ "
+    );
+    assert_eq!(
+        gutter(
+            highlighter()
+                .gutter_width_bounds(0, 5)
+                .gutter_overflow_marker('<'),
+            122
+        ),
+        "<3 │ "
+    );
+    // Only the marker fits, or nothing at all.
+    assert_eq!(gutter(highlighter().gutter_width_bounds(0, 4), 122), "… │ ");
+    assert_eq!(gutter(highlighter().gutter_width_bounds(0, 3), 122), " │ ");
+
+    let template = || {
+        highlighter().gutter_template(|line_number, style| {
+            vec![Span::styled(format!("{line_number:>5}|"), style)]
+        })
+    };
+    assert_eq!(gutter(template().gutter_width_bounds(0, 4), 122), "  12");
+    assert_eq!(
+        gutter(template().gutter_width_bounds(8, 8), 122),
+        "  123|  "
+    );
+}
+
+#[test]
+fn style_at() {
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let highlighter = Highlighter::new(theme.clone());
+    let syntax = SYNTAXES.find_syntax_by_name("Rust").unwrap();
+    let line = "let x = 1;\n";
+    let style_at = |offset| {
+        let mut lines = HighlightLines::new(syntax, &theme);
+        highlighter
+            .style_at(line, offset, &mut lines, &SYNTAXES)
+            .unwrap()
+    };
+    let keyword = style_at(0).unwrap();
+    assert_eq!(keyword.fg, Some(Color::Rgb(180, 142, 173)));
+    // Last byte of the keyword and the first byte after it.
+    assert_eq!(style_at(2), Some(keyword));
+    assert_ne!(style_at(3), Some(keyword));
+    assert_eq!(style_at(8).unwrap().fg, Some(Color::Rgb(208, 135, 112)));
+    // The line ending and anything past it are out of range.
+    assert_eq!(style_at(10), None);
+    assert_eq!(style_at(100), None);
+}
+
+#[test]
+fn monochrome() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .scope_override("keyword", Style::new().fg(Color::Red).bold())
+        .monochrome(true);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select * from users;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(25, 1, highlight);
+    assert_snapshot!("monochrome", backend);
+}
+
+#[test]
+fn gutter_background() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .gutter_background(Color::Rgb(20, 20, 20));
+    let highlight = highlighter
+        .highlight_lines(
+            ["select * from users;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(25, 1, highlight);
+    assert_eq!(backend.buffer()[(0, 0)].bg, Color::Rgb(20, 20, 20));
+    assert_eq!(backend.buffer()[(5, 0)].bg, Color::Rgb(43, 48, 59));
+    assert_snapshot!("gutter_background", backend);
+}
+
+#[test]
+fn linkify() {
+    let source = [
+        "-- docs: https://example.com/sql?q=1 (see also http://a.io/x).",
+        "select 1;",
+    ];
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .linkify(Style::new().underlined());
+    assert_eq!(
+        highlighter.find_links(source),
+        [
+            Link {
+                line: 0,
+                range: 9..36,
+                url: "https://example.com/sql?q=1".to_string(),
+            },
+            Link {
+                line: 0,
+                range: 47..60,
+                url: "http://a.io/x".to_string(),
+            },
+        ]
+    );
+
+    let highlight = highlighter
+        .highlight_lines(
+            source,
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let links: Vec<_> = highlight.lines[0]
+        .spans
+        .iter()
+        .filter(|span| span.style.add_modifier.contains(Modifier::UNDERLINED))
+        .map(|span| span.content.as_ref())
+        .collect();
+    assert_eq!(links, ["https://example.com/sql?q=1", "http://a.io/x"]);
+}
+
+#[test]
+fn highlight_range_styled() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .highlight_range_styled(0..2, Style::new().bg(Color::Red))
+        .highlight_range_styled(1..3, Style::new().bg(Color::Yellow))
+        .highlight_range(3..4)
+        .highlight_style(Style::new().bg(Color::Blue));
+    let highlight = highlighter
+        .highlight_lines(
+            LinesWithEndings::from("select a;\nselect b;\nselect c;\nselect d;"),
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(20, 4, highlight);
+    let buffer = backend.buffer();
+    assert_eq!(buffer[(6, 0)].bg, Color::Red);
+    // The later range wins where they overlap.
+    assert_eq!(buffer[(6, 1)].bg, Color::Yellow);
+    assert_eq!(buffer[(6, 2)].bg, Color::Yellow);
+    assert_eq!(buffer[(6, 3)].bg, Color::Blue);
+    assert_snapshot!("highlight_range_styled", backend);
+}
+
+#[test]
+fn highlight_span() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_numbers(false)
+        .highlight_span(0, 9..13, Style::new().underlined())
+        // Wide characters take up two columns each.
+        .highlight_span(1, 14..18, Style::new().underlined())
+        // A partly covered character is included.
+        .highlight_span(1, 9..10, Style::new().underlined());
+    let highlight = highlighter
+        .highlight_lines(
+            ["select a from b;", "select '日本' from b;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let underlined = |line: usize| -> Vec<String> {
+        highlight.lines[line]
+            .spans
+            .iter()
+            .filter(|span| span.style.add_modifier.contains(Modifier::UNDERLINED))
+            .map(|span| span.content.to_string())
+            .collect()
+    };
+    assert_eq!(underlined(0), ["from"]);
+    assert_eq!(underlined(1), ["日", "from"]);
+}
+
+#[test]
+fn highlight_code_only() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .highlight_range(0..1)
+        .highlight_code_only(true);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select a from b;", "select c from d;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let backend = draw(20, 2, highlight);
+    let buffer = backend.buffer();
+    let background = highlighter.get_background_color().unwrap();
+    let gutter_background = highlighter.get_gutter_background().unwrap();
+    for x in 0..5 {
+        assert_eq!(buffer[(x, 0)].bg, gutter_background);
+        assert_eq!(buffer[(x, 0)].style(), buffer[(x, 1)].style());
+    }
+    assert_eq!(buffer[(5, 0)].bg, Color::Yellow);
+    assert_eq!(buffer[(5, 1)].bg, background);
+}
+
+#[test]
+fn extended_ansi_index() {
+    let color = syntect::highlighting::Color {
+        r: 0x11,
+        g: 0,
+        b: 0,
+        a: 0,
+    };
+    assert_eq!(
+        Converter::new().syntect_color_to_tui(color),
+        Some(Color::Indexed(17))
+    );
+    let run_color = render::RunColor::from_syntect(color).unwrap();
+    assert_eq!(Color::from(run_color), Color::Indexed(17));
+}
+
+#[test]
+fn plain() {
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let foreground = Converter::new().syntect_color_to_tui(theme.settings.foreground.unwrap());
+    let highlighter = Highlighter::plain(theme);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select 'a' from b; -- comment"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let line = &highlight.lines[0];
+    assert_eq!(line.to_string(), " 1 │ select 'a' from b; -- comment");
+    let code = line.spans.last().unwrap();
+    assert_eq!(code.content, "select 'a' from b; -- comment");
+    assert_eq!(code.style, Style::new().fg(foreground.unwrap()));
+    assert_eq!(line.spans[1].content, "1");
+    assert_eq!(
+        line.spans[1].style.fg,
+        highlighter.get_line_number_style().fg
+    );
+}
+
+#[test]
+fn into_paragraph_truncated() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let text = highlighter
+        .highlight_lines(
+            ["select a,b,c from table;", "select 1;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let paragraph =
+        HighlightedText::new(text, highlighter.get_background_color()).into_paragraph_truncated(16);
+    let backend = draw(40, 2, paragraph);
+    let buffer = backend.buffer();
+    let row = |y| {
+        (0..40)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    };
+    assert_eq!(row(0), " 1 │ select a,b…");
+    assert_eq!(row(1), " 2 │ select 1;");
+}
+
+#[test]
+fn into_paragraph_truncated_marker() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let text = highlighter
+        .highlight_lines(
+            ["select a,b,c from table;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let row = |marker: Option<&str>| {
+        let paragraph = HighlightedText::new(text.clone(), highlighter.get_background_color())
+            .with_truncate_marker(marker.map(ToString::to_string))
+            .into_paragraph_truncated(16);
+        let backend = draw(40, 1, paragraph);
+        let buffer = backend.buffer();
+        (0..40)
+            .map(|x| buffer[(x, 0)].symbol())
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    };
+    assert_eq!(row(Some(">>")), " 1 │ select a,>>");
+    assert_eq!(row(None), " 1 │ select a,b,");
+
+    // Widgets from the highlighter style the marker like highlight_lines_width.
+    let paragraph = highlighter
+        .highlight_lines_widget(
+            ["select a,b,c from table;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap()
+        .into_paragraph_truncated(16);
+    let backend = draw(40, 1, paragraph);
+    let marker = &backend.buffer()[(15, 0)];
+    assert_eq!(marker.symbol(), "…");
+    assert_eq!(Some(marker.fg), highlighter.get_line_number_style().fg);
+}
+
+#[test]
+fn render_with_errors() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let errors = [CompilerError {
+        file: "query.sql".to_string(),
+        line: 1,
+        col_range: 8..9,
+        message: "unknown column".to_string(),
+    }];
+    let highlight = highlighter
+        .render_with_errors(
+            ["select x from b;", "select c from d;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+            &errors,
+        )
+        .unwrap();
+    let lines = &highlight.text().lines;
+    assert_eq!(lines.len(), 3);
+    let underlined: Vec<_> = lines[0]
+        .spans
+        .iter()
+        .filter(|span| span.style.add_modifier.contains(Modifier::UNDERLINED))
+        .map(|span| span.content.as_ref())
+        .collect();
+    assert_eq!(underlined, ["x"]);
+    assert_eq!(lines[1].to_string(), "            ^ unknown column");
+    assert_eq!(lines[2].to_string(), " 2 │ select c from d;");
+}
+
+#[test]
+fn render_with_errors_after_tab() {
+    let errors = [CompilerError {
+        file: "query.sql".to_string(),
+        line: 1,
+        col_range: 4..5,
+        message: "unexpected".to_string(),
+    }];
+    let highlight = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .render_with_errors(
+            ["aé\té"],
+            SYNTAXES.find_syntax_by_name("Plain Text").unwrap(),
+            &SYNTAXES,
+            &errors,
+        )
+        .unwrap();
+    let lines = &highlight.text().lines;
+    let underlined: Vec<_> = lines[0]
+        .spans
+        .iter()
+        .filter(|span| span.style.add_modifier.contains(Modifier::UNDERLINED))
+        .map(|span| span.content.as_ref())
+        .collect();
+    assert_eq!(underlined, ["é"]);
+    assert_eq!(lines[0].to_string(), " 1 │ aé  é");
+    assert_eq!(lines[1].to_string(), "         ^ unexpected");
+}
+
+#[test]
+fn wide_character_columns() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let fullwidth = |line_number: usize, style: Style| {
+        let digits: String = line_number
+            .to_string()
+            .chars()
+            .map(|c| char::from_u32(u32::from(c) - u32::from('0') + 0xFF10).unwrap())
+            .collect();
+        vec![Span::styled(format!("{digits}｜"), style)]
+    };
+    let text = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .gutter_template(fullwidth)
+        .wrap(WrapMode::Character, 12)
+        .highlight_lines(["select '世界世界';"], syntax, &SYNTAXES)
+        .unwrap();
+    let rows: Vec<_> = text.lines.iter().map(ToString::to_string).collect();
+    // The gutter is 4 columns wide, leaving 8 columns for code on each row.
+    assert_eq!(rows, ["１｜select '", "    世界世界", "    ';"]);
+    assert!(text.lines.iter().all(|line| line.width() <= 12));
+
+    let errors = [CompilerError {
+        file: "query.sql".to_string(),
+        line: 1,
+        col_range: 15..18,
+        message: "unknown column".to_string(),
+    }];
+    let highlight = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .render_with_errors(["\tselect '世界', frm;"], syntax, &SYNTAXES, &errors)
+        .unwrap();
+    let lines: Vec<_> = highlight
+        .text()
+        .lines
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(lines[0], " 1 │     select '世界', frm;");
+    assert_eq!(lines[1], "                        ^^^ unknown column");
+}
+
+#[test]
+fn crlf_line_endings() {
+    let syntax = SYNTAXES.find_syntax_by_name("Rust").unwrap();
+    let crlf = "// comment\r\nlet s = \"a\";\r\n\r\nfn main() {}\r\n";
+    let lf = crlf.replace("\r\n", "\n");
+    let no_cr = |text: &Text| {
+        text.lines
+            .iter()
+            .flat_map(|line| &line.spans)
+            .all(|span| !span.content.contains('\r'))
+    };
+
+    for preserve in [false, true] {
+        let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+            .preserve_line_endings(preserve);
+        let highlighted = highlighter
+            .highlight_lines(LinesWithEndings::from(crlf), syntax, &SYNTAXES)
+            .unwrap();
+        assert!(no_cr(&highlighted));
+        assert_eq!(highlighted.lines.len(), 4);
+        if !preserve {
+            assert_eq!(
+                highlighted,
+                highlighter
+                    .highlight_lines(LinesWithEndings::from(lf.as_str()), syntax, &SYNTAXES)
+                    .unwrap()
+            );
+        }
+
+        let scoped = highlighter
+            .with_scopes(true)
+            .highlight_lines_scoped(LinesWithEndings::from(crlf), syntax, &SYNTAXES)
+            .unwrap();
+        for ((_, spans), line) in scoped.iter().zip(LinesWithEndings::from(crlf)) {
+            assert!(spans.iter().all(|span| span.range.end <= line.len() - 2));
+        }
+    }
+
+    let plain = Highlighter::plain(THEMES.themes["base16-ocean.dark"].clone())
+        .highlight_lines(LinesWithEndings::from(crlf), syntax, &SYNTAXES)
+        .unwrap();
+    assert!(no_cr(&plain));
+
+    let theme = &THEMES.themes["base16-ocean.dark"];
+    let lines =
+        render::styled_lines(theme, LinesWithEndings::from(crlf), syntax, &SYNTAXES).unwrap();
+    assert!(lines.iter().flatten().all(|run| !run.text.contains('\r')));
+}
+
+#[test]
+fn keep_final_newline() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let count = |highlighter: &Highlighter, source: &str| {
+        let lines = highlighter
+            .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+            .unwrap()
+            .lines
+            .len();
+        let reader = highlighter
+            .highlight_reader(source.as_bytes(), syntax, &SYNTAXES)
+            .unwrap()
+            .lines
+            .len();
+        assert_eq!(lines, reader);
+        lines
+    };
+
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    assert_eq!(count(&highlighter, "a\n"), 1);
+    assert_eq!(count(&highlighter, "a"), 1);
+
+    let highlighter = highlighter.keep_final_newline(true);
+    assert_eq!(count(&highlighter, "a\n"), 2);
+    assert_eq!(count(&highlighter, "a"), 1);
+}
+
+#[test]
+fn trailing_blank_line() {
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let count = |source: &[&str]| {
+        let lines = highlighter
+            .highlight_lines(source.iter().copied(), syntax, &SYNTAXES)
+            .unwrap()
+            .lines
+            .len();
+        let borrowed = highlighter
+            .highlight_lines_borrowed(source, syntax, &SYNTAXES)
+            .unwrap()
+            .lines
+            .len();
+        assert_eq!(lines, borrowed);
+        lines
+    };
+
+    // A blank last line is kept when the lines don't carry their own newlines.
+    let lines: Vec<_> = "a\n\n".lines().collect();
+    assert_eq!(count(&lines), 2);
+    assert_eq!(count(&["a", ""]), 2);
+    // An empty item after a line ending with a newline is only the remainder of the split.
+    assert_eq!(count(&["a\n", ""]), 1);
+}
+
+#[test]
+fn highlight_lines_sized() {
+    let source: Vec<_> = (0..500)
+        .map(|i| format!("select {i} from table{i}; -- row {i}"))
+        .collect();
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let sized = highlighter
+        .highlight_lines_sized(
+            source.iter().map(String::as_str),
+            source.len(),
+            syntax,
+            &SYNTAXES,
+        )
+        .unwrap();
+    let expected = highlighter
+        .highlight_lines(source.iter().map(String::as_str), syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(sized.lines.len(), 500);
+    assert_eq!(sized, expected);
+}
+
+#[test]
+fn line_number_separator_fn() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .current_line(Some(1))
+        .line_number_separator_fn(|_, flags| {
+            if flags.is_current {
+                Span::raw("┣")
+            } else {
+                Span::raw("│")
+            }
+        });
+    let highlight = highlighter
+        .highlight_lines(
+            ["select a from b;", "select c from d;", "select e from f;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let separators: Vec<_> = highlight
+        .lines
+        .iter()
+        .map(|line| line.spans[3].content.as_ref())
+        .collect();
+    assert_eq!(separators, ["│", "┣", "│"]);
+}
+
+#[test]
+fn min_content_width() {
+    let highlighter =
+        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).min_content_width(20);
+    let highlight = highlighter
+        .highlight_lines(
+            ["select 1;", "select a,b,c,d,e from table;"],
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    // The gutter is 5 columns wide.
+    assert_eq!(highlight.lines[0].width(), 25);
+    assert_eq!(highlight.lines[1].width(), 33);
+
+    let background = highlighter.get_background_color().unwrap();
+    let gutter_background = highlighter.get_gutter_background().unwrap();
+    let backend = draw(40, 2, Paragraph::new(highlight));
+    let buffer = backend.buffer();
+    for x in 0..5 {
+        assert_eq!(buffer[(x, 0)].bg, gutter_background);
+    }
+    for x in 5..25 {
+        assert_eq!(buffer[(x, 0)].bg, background);
+    }
+    assert_eq!(buffer[(25, 0)].bg, Color::Reset);
+}
+
+#[test]
+fn snippet() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let paragraph = highlighter
+        .snippet("select a from b;\n\n  \n", syntax, &SYNTAXES, "query.sql")
+        .unwrap();
+
+    let text = highlighter
+        .highlight_lines(["select a from b;\n"], syntax, &SYNTAXES)
+        .unwrap();
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .title("query.sql");
+    let bg = highlighter.get_background_color().unwrap();
+    assert_eq!(paragraph, Paragraph::new(text).bg(bg).block(block));
+}
+
+#[test]
+fn version_invalidation() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let mut highlights = 0;
+    let mut cache: Option<HighlightedText> = None;
+    let mut render = |source: &str, version: u64| {
+        if cache.as_ref().is_none_or(|cached| cached.is_stale(version)) {
+            highlights += 1;
+            let text = highlighter
+                .highlight_lines([source], syntax, &SYNTAXES)
+                .unwrap();
+            cache = Some(
+                HighlightedText::new(text, highlighter.get_background_color())
+                    .with_version(version),
+            );
+        }
+        cache.clone().unwrap()
+    };
+
+    let first = render("select a from b;", 1);
+    let second = render("select a from b;", 1);
+    assert_eq!(first, second);
+    let third = render("select c from d;", 2);
+    assert_eq!(third.version(), 2);
+    assert_ne!(first, third);
+    assert_eq!(highlights, 2);
+}
+
+#[test]
+fn highlight_with_minimap() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).minimap(4);
+    let source = ["select a,b,c from table;", "", "select 'text' from table2;"];
+    let (code, minimap) = highlighter
+        .highlight_with_minimap(
+            source,
+            SYNTAXES.find_syntax_by_name("SQL").unwrap(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    assert_eq!(code.text().lines.len(), 3);
+    let rows = &minimap.text().lines;
+    assert_eq!(rows.len(), 3);
+    for row in rows {
+        assert_eq!(row.width(), 4);
+    }
+    assert!(rows[0].spans.iter().all(|span| span.content == "█"));
+    assert!(rows[0].spans[0].style.fg.is_some());
+    assert_eq!(rows[1].to_string().trim(), "");
+    assert_ne!(rows[2].spans[1].style.fg, rows[0].spans[1].style.fg);
+}
+
+#[test]
+fn theme_for() {
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let markdown_theme = THEMES.themes["InspiredGitHub"].clone();
+    let highlighter = Highlighter::new(theme.clone()).theme_for("Markdown", markdown_theme.clone());
+    let markdown = SYNTAXES.find_syntax_by_name("Markdown").unwrap();
+    let sql = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+
+    let highlight = highlighter
+        .highlight_lines(["# Title *emphasis*"], markdown, &SYNTAXES)
+        .unwrap();
+    let expected = Highlighter::new(markdown_theme)
+        .highlight_lines(["# Title *emphasis*"], markdown, &SYNTAXES)
+        .unwrap();
+    assert_eq!(highlight, expected);
+    assert_eq!(highlight.lines[0].style.bg, Some(Color::Rgb(255, 255, 255)));
+
+    let highlight = highlighter
+        .highlight_lines(["select a from b;"], sql, &SYNTAXES)
+        .unwrap();
+    let expected = Highlighter::new(theme)
+        .highlight_lines(["select a from b;"], sql, &SYNTAXES)
+        .unwrap();
+    assert_eq!(highlight, expected);
+    assert_eq!(highlight.lines[0].style.bg, Some(Color::Rgb(43, 48, 59)));
+}
+
+#[test]
+fn east_asian_width() {
+    let theme = THEMES.themes["base16-ocean.dark"].clone();
+    let syntax = SYNTAXES.find_syntax_plain_text();
+    for (east_asian_width, expected_tab, expected_truncated) in
+        [(false, "○   x", "○○○○○"), (true, "○  x", "○○…")]
+    {
+        let highlighter = Highlighter::new(theme.clone())
+            .line_numbers(false)
+            .tab_width(4)
+            .east_asian_width(east_asian_width);
+        let text = highlighter
+            .highlight_lines(["○\tx"], syntax, &SYNTAXES)
+            .unwrap();
+        assert_eq!(text.lines[0].to_string(), expected_tab);
+
+        let text = highlighter
+            .highlight_lines(["○○○○○"], syntax, &SYNTAXES)
+            .unwrap();
+        let paragraph = HighlightedText::new(text, highlighter.get_background_color())
+            .with_east_asian_width(east_asian_width)
+            .into_paragraph_truncated(6);
+        let backend = draw(10, 1, paragraph);
+        let row: String = (0..10).map(|x| backend.buffer()[(x, 0)].symbol()).collect();
+        assert_eq!(row.trim_end(), expected_truncated);
+    }
+}
+
+#[test]
+fn wrap() {
+    let source = ["select alpha, beta from gamma;", "select 1;"];
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let rows = |mode| {
+        highlighter
+            .clone()
+            .wrap(mode, 20)
+            .highlight_lines(source, syntax, &SYNTAXES)
+            .unwrap()
+            .lines
+    };
+
+    let word = rows(WrapMode::Word);
+    let text: Vec<_> = word.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        text,
+        [
+            " 1 │ select alpha, ",
+            "     beta from ",
+            "     gamma;",
+            " 2 │ select 1;"
+        ]
+    );
+    assert_eq!(word[1].spans[0].style, word[0].spans[0].style);
+
+    let character = rows(WrapMode::Character);
+    let text: Vec<_> = character.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        text,
+        [
+            " 1 │ select alpha, b",
+            "     eta from gamma;",
+            " 2 │ select 1;"
+        ]
+    );
+    let split_start = character[0].spans.last().unwrap();
+    let split_end = &character[1].spans[1];
+    assert_eq!(split_start.content, " alpha, b");
+    assert_eq!(split_end.content, "eta ");
+    assert_eq!(split_start.style, split_end.style);
+    let keyword_style = character[0].spans[5].style;
+    let from = character[1]
+        .spans
+        .iter()
+        .find(|span| span.content == "from")
+        .unwrap();
+    assert_eq!(from.style, keyword_style);
+}
+
+#[test]
+fn relative_line_numbers() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .line_number_padding(5)
+        .relative_line_numbers(2);
+    let text = highlighter
+        .highlight_lines(
+            ["a", "b", "c", "d", "e"],
+            SYNTAXES.find_syntax_plain_text(),
+            &SYNTAXES,
+        )
+        .unwrap();
+    let gutters: Vec<_> = text
+        .lines
+        .iter()
+        .map(|line| {
+            line.spans[..5]
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        })
+        .collect();
+    assert_eq!(gutters, ["  2 │ ", "  1 │ ", "  3 │ ", "  1 │ ", "  2 │ "]);
+    assert_eq!(
+        text.lines[0].spans[1].style.fg,
+        highlighter.get_line_number_style().fg
+    );
+}
+
+#[test]
+fn highlight_with_cache() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let mut cache = LineCache::new();
+    let mut lines = vec!["select a from b;", "select c from d;", "select e from f;"];
+
+    let text = highlighter
+        .highlight_with_cache(&lines, &mut cache, 0, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(
+        text,
+        highlighter
+            .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+            .unwrap()
+    );
+    assert_eq!(cache.len(), 3);
+
+    // Opening a block comment changes how every following line is parsed.
+    lines[1] = "/* select c from d;";
+    let text = highlighter
+        .highlight_with_cache(&lines, &mut cache, 1, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(
+        text,
+        highlighter
+            .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+            .unwrap()
+    );
+    assert_eq!(text.lines[2].to_string(), " 3 │ select e from f;");
+}
+
+#[test]
+fn highlight_with_cache_line_number_width() {
+    let highlighter =
+        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).auto_line_number_width(true);
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let mut cache = LineCache::new();
+    let mut lines = vec!["select a;"; 9];
+
+    let text = highlighter
+        .highlight_with_cache(&lines, &mut cache, 0, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(text.lines[0].to_string(), "1 │ select a;");
+
+    // The tenth line widens the gutter, so the cached rows are highlighted again.
+    lines.push("select b;");
+    let text = highlighter
+        .highlight_with_cache(&lines, &mut cache, 9, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(
+        text,
+        highlighter
+            .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+            .unwrap()
+    );
+    assert_eq!(text.lines[0].to_string(), " 1 │ select a;");
+    assert_eq!(text.lines[9].to_string(), "10 │ select b;");
+}
+
+#[test]
+fn highlight_with_cache_brackets() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .match_brackets((2, 0), Style::new().bg(Color::Red));
+    let syntax = SYNTAXES.find_syntax_by_name("Rust").unwrap();
+    let mut cache = LineCache::new();
+    let mut lines = vec!["fn main() {", "    let a = 1;", "}"];
+
+    let text = highlighter
+        .highlight_with_cache(&lines, &mut cache, 0, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(
+        text,
+        highlighter
+            .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+            .unwrap()
+    );
+
+    // Moving the opening bracket changes the highlight on a line before the edit.
+    lines[0] = "fn main() ";
+    lines[1] = "{ let a = 1;";
+    let text = highlighter
+        .highlight_with_cache(&lines, &mut cache, 1, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(
+        text,
+        highlighter
+            .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+            .unwrap()
+    );
+}
+
+#[test]
+fn highlight_with_cache_decorations() {
+    let base = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let mut cache = LineCache::new();
+    let lines = ["select a from b;", "select c from d;", "select e from f;"];
+    let configs = [
+        base.clone(),
+        base.clone().current_line(Some(1)),
+        base.clone().current_line(Some(2)).relative_line_numbers(2),
+        base.clone().selection((0, 2), (1, 4)),
+        base.clone()
+            .highlight_span(2, 0..6, Style::new().bg(Color::Red)),
+        base.clone()
+            .search_highlight("from", Style::new().bg(Color::Blue))
+            .current_match(1, Style::new().bg(Color::Green)),
+        base.clone()
+            .search_highlight("from", Style::new().bg(Color::Blue))
+            .current_match(2, Style::new().bg(Color::Green)),
+        base.clone()
+            .search_highlight("select", Style::new().bg(Color::Blue)),
+        base,
+    ];
+    for highlighter in configs {
+        // Nothing was edited, so only the decorations changed.
+        let text = highlighter
+            .highlight_with_cache(&lines, &mut cache, lines.len(), syntax, &SYNTAXES)
+            .unwrap();
+        assert_eq!(
+            text,
+            highlighter
+                .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+                .unwrap()
+        );
+    }
+}
+
+#[test]
+fn highlight_with_cache_distant_brackets() {
+    let syntax = SYNTAXES.find_syntax_by_name("Rust").unwrap();
+    let mut lines = vec!["fn main() {", "    /* } */"];
+    lines.extend(["    let a = (1);"; 20]);
+    lines.push("}");
+    let mut cache = LineCache::new();
+    for cursor in [(0, 0), (22, 0), (0, 10), (5, 12), (5, 14), (1, 7)] {
+        let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+            .match_brackets(cursor, Style::new().bg(Color::Red));
+        let text = highlighter
+            .highlight_with_cache(&lines, &mut cache, lines.len(), syntax, &SYNTAXES)
+            .unwrap();
+        assert_eq!(
+            text,
+            highlighter
+                .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+                .unwrap(),
+            "cursor {cursor:?}"
+        );
+    }
+}
+
+#[test]
+fn highlight_viewport() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let lines = [
+        "select a from b;",
+        "/* select c",
+        "from d; */",
+        "select e from f;",
+        "select g from h;",
+    ];
+    let full = highlighter
+        .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+        .unwrap();
+    let viewport = highlighter
+        .highlight_viewport(&lines, 2..4, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(viewport.lines, full.lines[2..4]);
+    assert_eq!(viewport.lines[0].to_string(), " 3 │ from d; */");
+
+    let viewport = highlighter
+        .highlight_viewport(&lines, 3..10, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(viewport.lines, full.lines[3..]);
+}
+
+#[test]
+fn highlight_lines_borrowed() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone())
+        .search_highlight("c", Style::new().bg(Color::Yellow));
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = "select a from b;\n/* select c\nfrom d; */\n\tselect e from f;\n";
+    let lines: Vec<_> = source.split('\n').collect();
+    let owned = highlighter
+        .highlight_lines(lines.iter().copied(), syntax, &SYNTAXES)
+        .unwrap();
+    let borrowed = highlighter
+        .highlight_lines_borrowed(&lines, syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(borrowed, owned);
+    assert!(matches!(
+        borrowed.lines[2].spans.last().unwrap().content,
+        Cow::Borrowed(_)
+    ));
+
+    // Only the span split by the match is copied.
+    let lines = ["select c from d;"];
+    let borrowed = highlighter
+        .highlight_lines_borrowed(&lines, syntax, &SYNTAXES)
+        .unwrap();
+    let content = |text| {
+        &borrowed.lines[0]
+            .spans
+            .iter()
+            .find(|span| span.content == text)
+            .unwrap()
+            .content
+    };
+    assert!(matches!(content("from"), Cow::Borrowed(_)));
+    assert!(matches!(content("c"), Cow::Owned(_)));
+}
+
+#[test]
+fn highlight_widget() {
+    let highlighter = Highlighter::new(THEMES.themes["base16-ocean.dark"].clone());
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let source = "select a from b;\nselect c from d;";
+    let text = highlighter
+        .highlight_lines(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+
+    let widget = highlighter
+        .highlight_lines_widget(LinesWithEndings::from(source), syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(widget.text(), &text);
+    assert_eq!(widget.background(), highlighter.get_background_color());
+
+    let widget = highlighter
+        .highlight_reader_widget(source.as_bytes(), syntax, &SYNTAXES)
+        .unwrap();
+    assert_eq!(widget.text(), &text);
+    assert_eq!(widget.background(), highlighter.get_background_color());
+}
+
+#[test]
+fn tab_width_default() {
+    let syntax = SYNTAXES.find_syntax_by_name("Rust").unwrap();
+    let source = ["\tlet a = 1;", "  \tlet b\t= \"x\ty\";", "ab\t\tc"];
+    let highlighter =
+        Highlighter::new(THEMES.themes["base16-ocean.dark"].clone()).line_numbers(false);
+    let text = highlighter
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let lines: Vec<_> = text.lines.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        lines,
+        ["    let a = 1;", "    let b   = \"x    y\";", "ab      c"]
+    );
+    let string = text.lines[1]
+        .spans
+        .iter()
+        .find(|span| span.content.contains("x    y"))
+        .unwrap();
+    let plain = highlighter
+        .clone()
+        .tab_width(0)
+        .highlight_lines(source, syntax, &SYNTAXES)
+        .unwrap();
+    let unexpanded = plain.lines[1]
+        .spans
+        .iter()
+        .find(|span| span.content.contains("x\ty"))
+        .unwrap();
+    assert_eq!(string.style, unexpanded.style);
+    assert_eq!(plain.lines[0].to_string(), "\tlet a = 1;");
+}
+
 fn draw<W>(width: u16, height: u16, widget: W) -> TestBackend
 where
     W: Widget,
@@ -0,0 +1,86 @@
+use std::sync::LazyLock;
+
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use tui_syntax_highlight::render::{self, RunColor};
+use tui_syntax_highlight::{ColorDepth, Converter};
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+#[test]
+fn to_html() {
+    let theme = &THEMES.themes["base16-ocean.dark"];
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let lines =
+        render::styled_lines(theme, ["select a from b where a < 1;"], syntax, &SYNTAXES).unwrap();
+    let background = theme.settings.background.and_then(RunColor::from_syntect);
+
+    let html = render::to_html(&lines, background);
+    assert!(html.starts_with("<pre style=\"background-color:#2b303b;\">"));
+    assert!(html.ends_with("</pre>"));
+    assert!(html.contains("<span style=\"color:#b48ead;\">select</span>"));
+    assert!(html.contains("&lt;"));
+    assert!(!html.contains(" < "));
+}
+
+#[test]
+fn converter_styled_lines() {
+    let theme = &THEMES.themes["base16-ocean.dark"];
+    let syntax = SYNTAXES.find_syntax_by_name("SQL").unwrap();
+    let lines = Converter::new()
+        .downsample(ColorDepth::Ansi256)
+        .styled_lines(theme, ["select a from b;"], syntax, &SYNTAXES)
+        .unwrap();
+    let select = &lines[0][0];
+    assert_eq!(select.text, "select");
+    // #b48ead is closest to index 139 (#af87af) in the color cube.
+    assert_eq!(select.fg, Some(RunColor::Indexed(139)));
+    assert!(render::to_html(&lines, None).contains("color:#af87af;"));
+
+    let lines = Converter::new()
+        .downsample(ColorDepth::Monochrome)
+        .styled_lines(theme, ["select a from b;"], syntax, &SYNTAXES)
+        .unwrap();
+    assert!(
+        lines[0]
+            .iter()
+            .all(|run| run.fg.is_none() && run.bg.is_none())
+    );
+}
+
+#[test]
+fn to_ansi_string() {
+    let lines = vec![
+        vec![
+            render::StyledRun {
+                text: "fn".to_string(),
+                fg: Some(RunColor::Indexed(1)),
+                bg: None,
+                bold: true,
+                italic: false,
+                underline: false,
+            },
+            render::StyledRun {
+                text: " main".to_string(),
+                fg: None,
+                bg: None,
+                bold: false,
+                italic: false,
+                underline: false,
+            },
+        ],
+        vec![render::StyledRun {
+            text: "}".to_string(),
+            fg: Some(RunColor::Rgb(1, 2, 3)),
+            bg: Some(RunColor::Indexed(200)),
+            bold: false,
+            italic: false,
+            underline: true,
+        }],
+    ];
+    assert_eq!(
+        render::to_ansi_string(&lines),
+        "\x1b[1;31mfn\x1b[0m main\n\x1b[4;38;2;1;2;3;48;5;200m}\x1b[0m"
+    );
+}